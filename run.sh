@@ -1,2 +1,2 @@
 cp -r resources target/debug/resources
-cargo run
+cargo run --bin crabber
@@ -0,0 +1,177 @@
+/*
+Copyright (c) 2018 Matt Carnovale, Julie Rutherford-Fields, Joshua Sander
+This work is available under the "MIT License”.
+Please see the file LICENSE in this distribution
+for license terms.
+*/
+
+use constants::{BIG_CRAB_IMAGE, FONT_FILE, SMALL_CRAB_IMAGE, SPRITE_SHEET, TEXT_CACHE_SIZE,
+                WIN_W};
+use ggez::graphics::{self, Font, Image, Point2, Text};
+use ggez::{Context, GameResult};
+use sprites::SpriteSheet;
+use std::collections::HashMap;
+use theme::Theme;
+
+/// The sizes the game's font is drawn at
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum FontSize {
+    /// The game's name on the title screen
+    Title,
+    /// Screen titles and big announcements
+    Large,
+    /// Messages that need to stand out less
+    Medium,
+    /// Menu items and tables
+    Menu,
+    /// The HUD and hints along the bottom
+    Small,
+}
+
+impl FontSize {
+    /// Returns the size in points
+    fn points(&self) -> u32 {
+        match *self {
+            FontSize::Title => 56,
+            FontSize::Large => 48,
+            FontSize::Medium => 24,
+            FontSize::Menu => 20,
+            FontSize::Small => 16,
+        }
+    }
+}
+
+/// A piece of text drawn every frame that only changes now and then, like
+/// the score. It is only rendered again when what it says has changed.
+pub struct CachedText {
+    contents: String,
+    text: Option<Text>,
+}
+
+impl CachedText {
+    /// Creates the text with nothing rendered yet
+    pub fn new() -> CachedText {
+        CachedText {
+            contents: String::new(),
+            text: None,
+        }
+    }
+
+    /// Returns the rendered text, rendering it again first if it has changed
+    fn get(&mut self, ctx: &mut Context, font: &Font, contents: &str) -> GameResult<&Text> {
+        if self.text.is_none() || self.contents != contents {
+            self.text = Some(Text::new(ctx, contents, font)?);
+            self.contents = contents.to_string();
+        }
+        Ok(self.text.as_ref().unwrap())
+    }
+}
+
+impl Default for CachedText {
+    fn default() -> CachedText {
+        CachedText::new()
+    }
+}
+
+/// Images, fonts and rendered text, loaded once at startup
+/// rather than every time something is drawn
+pub struct Assets {
+    pub big_crab: Image,
+    pub small_crab: Image,
+    /// A single white pixel, stretched and tinted to draw particles
+    pub pixel: Image,
+    /// The traffic and river art, or None to draw rectangles instead
+    pub sprites: Option<SpriteSheet>,
+    fonts: HashMap<FontSize, Font>,
+    texts: HashMap<(FontSize, String), Text>,
+    lives: CachedText,
+    score: CachedText,
+}
+
+impl Assets {
+    /// Loads every image and font from the resources directory,
+    /// using any images the theme has in place of the usual ones
+    pub fn load(ctx: &mut Context, theme: &Theme) -> GameResult<Assets> {
+        let mut fonts = HashMap::new();
+        for size in [
+            FontSize::Title,
+            FontSize::Large,
+            FontSize::Medium,
+            FontSize::Menu,
+            FontSize::Small,
+        ].iter()
+        {
+            fonts.insert(*size, Font::new(ctx, FONT_FILE, size.points())?);
+        }
+
+        Ok(Assets {
+            big_crab: themed_image(ctx, &theme.big_crab, BIG_CRAB_IMAGE)?,
+            small_crab: themed_image(ctx, &theme.small_crab, SMALL_CRAB_IMAGE)?,
+            pixel: Image::from_rgba8(ctx, 1, 1, &[255, 255, 255, 255])?,
+            sprites: theme
+                .sprite_sheet
+                .as_ref()
+                .and_then(|path| SpriteSheet::load(ctx, path))
+                .or_else(|| SpriteSheet::load(ctx, SPRITE_SHEET)),
+            fonts: fonts,
+            texts: HashMap::new(),
+            lives: CachedText::new(),
+            score: CachedText::new(),
+        })
+    }
+
+    /// Returns the game's font at one of its sizes
+    pub fn font(&self, size: FontSize) -> &Font {
+        &self.fonts[&size]
+    }
+
+    /// Returns text rendered in the game's font. Each piece of text
+    /// is only rendered the first time it is asked for.
+    pub fn text(&mut self, ctx: &mut Context, size: FontSize, contents: &str) -> GameResult<&Text> {
+        let key = (size, contents.to_string());
+        if !self.texts.contains_key(&key) {
+            // Text that keeps changing would otherwise pile up forever
+            if self.texts.len() >= TEXT_CACHE_SIZE {
+                self.texts.clear();
+            }
+            let text = Text::new(ctx, contents, &self.fonts[&size])?;
+            self.texts.insert(key.clone(), text);
+        }
+        Ok(&self.texts[&key])
+    }
+
+    /// Returns the lives shown in the HUD, only rendered again when they change
+    pub fn lives_text(&mut self, ctx: &mut Context, contents: &str) -> GameResult<&Text> {
+        self.lives.get(ctx, &self.fonts[&FontSize::Small], contents)
+    }
+
+    /// Returns the score shown in the HUD, only rendered again when it changes
+    pub fn score_text(&mut self, ctx: &mut Context, contents: &str) -> GameResult<&Text> {
+        self.score.get(ctx, &self.fonts[&FontSize::Small], contents)
+    }
+
+    /// Draws text centered across the window with its top at the given height
+    pub fn draw_centered(
+        &mut self,
+        ctx: &mut Context,
+        size: FontSize,
+        contents: &str,
+        y: f32,
+    ) -> GameResult<()> {
+        let text = self.text(ctx, size, contents)?;
+        let x = WIN_W as f32 / 2.0 - text.width() as f32 / 2.0;
+        graphics::draw(ctx, text, Point2::new(x, y), 0.0)
+    }
+}
+
+/// Loads a theme's image in place of the usual one. The usual one is used
+/// if the theme doesn't have its own or its own can't be loaded.
+fn themed_image(ctx: &mut Context, path: &Option<String>, usual: &str) -> GameResult<Image> {
+    if let Some(ref path) = *path {
+        match Image::new(ctx, path) {
+            Ok(image) => return Ok(image),
+            Err(e) => println!("Could not load {}, using {} instead: {}", path, usual, e),
+        }
+    }
+    Image::new(ctx, usual)
+}
@@ -0,0 +1,156 @@
+/*
+Copyright (c) 2018 Matt Carnovale, Julie Rutherford-Fields, Joshua Sander
+This work is available under the "MIT License”.
+Please see the file LICENSE in this distribution
+for license terms.
+*/
+
+use characters::DeathCause;
+use events::{GameEvent, GameEventListener};
+use ggez::Context;
+use ggez::audio::Source;
+
+/// The background tracks that can be looping
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum MusicTrack {
+    Menu,
+    Game,
+}
+
+/// Holds every sound in the game. Any sound that can't be loaded,
+/// because its file is missing or unreadable, is left as None and the
+/// game carries on silently without it. Having no sounds at all is fine
+/// too, ready for when the game can start without an audio device.
+pub struct Sounds {
+    hop: Option<Source>,
+    splat: Option<Source>,
+    splash: Option<Source>,
+    cubby: Option<Source>,
+    fanfare: Option<Source>,
+    menu_music: Option<Source>,
+    game_music: Option<Source>,
+    playing: Option<MusicTrack>,
+    music_volume: f32,
+    effects_volume: f32,
+}
+
+impl Sounds {
+    /// Loads every sound effect and music track
+    pub fn load(ctx: &mut Context, music_volume: f32, effects_volume: f32) -> Sounds {
+        let mut sounds = Sounds {
+            hop: Sounds::load_source(ctx, "/sounds/hop.wav"),
+            splat: Sounds::load_source(ctx, "/sounds/splat.wav"),
+            splash: Sounds::load_source(ctx, "/sounds/splash.wav"),
+            cubby: Sounds::load_source(ctx, "/sounds/cubby.wav"),
+            fanfare: Sounds::load_source(ctx, "/sounds/fanfare.wav"),
+            menu_music: Sounds::load_source(ctx, "/sounds/menu_music.wav"),
+            game_music: Sounds::load_source(ctx, "/sounds/game_music.wav"),
+            playing: None,
+            music_volume,
+            effects_volume,
+        };
+
+        for music in vec![&mut sounds.menu_music, &mut sounds.game_music] {
+            if let Some(ref mut music) = *music {
+                music.set_repeat(true);
+            }
+        }
+        sounds.set_music_volume(music_volume);
+        sounds.set_effects_volume(effects_volume);
+        sounds
+    }
+
+    /// Loads a single sound, reporting rather than failing if it can't be
+    fn load_source(ctx: &mut Context, path: &str) -> Option<Source> {
+        match Source::new(ctx, path) {
+            Ok(source) => Some(source),
+            Err(e) => {
+                println!("Could not load sound {}: {}", path, e);
+                None
+            }
+        }
+    }
+
+    /// Plays a sound if it was loaded
+    fn play(sound: &Option<Source>) {
+        if let Some(ref sound) = *sound {
+            if let Err(e) = sound.play() {
+                println!("Could not play sound: {}", e);
+            }
+        }
+    }
+
+    /// Starts looping a music track, stopping whichever one was playing.
+    /// Asking for the track that is already playing does nothing.
+    pub fn play_music(&mut self, track: MusicTrack) {
+        if self.playing == Some(track) {
+            return;
+        }
+        self.stop_music();
+        match track {
+            MusicTrack::Menu => Sounds::play(&self.menu_music),
+            MusicTrack::Game => Sounds::play(&self.game_music),
+        }
+        self.playing = Some(track);
+    }
+
+    /// Stops any music that is playing
+    pub fn stop_music(&mut self) {
+        for music in vec![&mut self.menu_music, &mut self.game_music] {
+            if let Some(ref mut music) = *music {
+                music.stop();
+            }
+        }
+        self.playing = None;
+    }
+
+    /// Returns the music volume, from 0.0 to 1.0
+    pub fn get_music_volume(&self) -> f32 {
+        self.music_volume
+    }
+
+    /// Sets the volume of both music tracks
+    pub fn set_music_volume(&mut self, volume: f32) {
+        self.music_volume = volume;
+        for music in vec![&mut self.menu_music, &mut self.game_music] {
+            if let Some(ref mut music) = *music {
+                music.set_volume(volume);
+            }
+        }
+    }
+
+    /// Returns the sound effects volume, from 0.0 to 1.0
+    pub fn get_effects_volume(&self) -> f32 {
+        self.effects_volume
+    }
+
+    /// Sets the volume of every sound effect
+    pub fn set_effects_volume(&mut self, volume: f32) {
+        self.effects_volume = volume;
+        for effect in vec![
+            &mut self.hop,
+            &mut self.splat,
+            &mut self.splash,
+            &mut self.cubby,
+            &mut self.fanfare,
+        ] {
+            if let Some(ref mut effect) = *effect {
+                effect.set_volume(volume);
+            }
+        }
+    }
+}
+
+impl GameEventListener for Sounds {
+    fn on_event(&mut self, event: &GameEvent) {
+        match *event {
+            GameEvent::Hopped => Sounds::play(&self.hop),
+            GameEvent::Died(DeathCause::Drowned)
+            | GameEvent::Died(DeathCause::CarriedOffscreen) => Sounds::play(&self.splash),
+            GameEvent::Died(_) => Sounds::play(&self.splat),
+            GameEvent::CubbyFilled(_) => Sounds::play(&self.cubby),
+            GameEvent::LevelCleared => Sounds::play(&self.fanfare),
+            GameEvent::ReachedNewRow | GameEvent::GameOver | GameEvent::ExtraLife => {}
+        }
+    }
+}
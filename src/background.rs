@@ -5,12 +5,15 @@ Please see the file LICENSE in this distribution
 for license terms.
 */
 
-use constants::{CUB_NUM, END, MID_ROW, NUM_LANE, NUM_LOG, NUM_ROW, RIVER, ROAD, SQUARE_SIZE,
-                WIN_H, WIN_W};
+use assets::{Assets, FontSize};
+use constants::{CUB_NUM, END, MID_ROW, NUM_LANE, NUM_LOG, NUM_ROW, SQUARE_SIZE, WIN_H, WIN_W};
 
-use ggez::graphics;
+use ggez::graphics::{self, Color, DrawMode, DrawParam};
 use ggez::{Context, GameResult};
-use sprites::Rectangle;
+use leaderboard::ScoreEntry;
+use menu::{MenuChoice, MenuList};
+use sprites::{DrawBatch, Rectangle};
+use theme::Theme;
 
 /// Represents the 'road' region of the game environment
 pub struct Road {
@@ -26,6 +29,7 @@ pub struct River {
 pub struct Cubbie {
     form: Rectangle,
     is_occupied: bool,
+    owner: Option<u32>,
 }
 
 /// Represents a vector of cubbies which is scalable
@@ -38,6 +42,18 @@ pub struct Cubbies {
 /// Represents the game start menu
 pub struct Menu {}
 
+/// Represents the pause menu drawn over a game in progress
+pub struct PauseMenu {}
+
+/// Represents the options screen for changing settings
+pub struct OptionsMenu {}
+
+/// Represents the screen shown while waiting for an online game to start
+pub struct Lobby {}
+
+/// Represents the high score table
+pub struct HighScores {}
+
 /// Implements the road.
 impl Road {
     /// Creates a new road which is scalable depending
@@ -49,15 +65,13 @@ impl Road {
                 h as f32 - (MID_ROW - 1) as f32 * SQUARE_SIZE,
                 w as f32,
                 NUM_LANE as f32 * SQUARE_SIZE,
-                ROAD,
             ),
         }
     }
 
-    /// Draws the road graphic on screen
-    pub fn draw(&mut self, ctx: &mut Context) -> GameResult<()> {
-        self.form.draw(ctx)?;
-        Ok(())
+    /// Adds the road graphic to the background layer
+    pub fn draw(&self, batch: &mut DrawBatch, theme: &Theme) {
+        batch.add_rectangle(&self.form, theme.road());
     }
 }
 
@@ -72,15 +86,13 @@ impl River {
                 h as f32 - (NUM_ROW as f32 - 3.0) * SQUARE_SIZE,
                 w as f32,
                 NUM_LOG as f32 * SQUARE_SIZE,
-                RIVER,
             ),
         }
     }
 
-    /// Draws the river graphic on screen
-    pub fn draw(&mut self, ctx: &mut Context) -> GameResult<()> {
-        self.form.draw(ctx)?;
-        Ok(())
+    /// Adds the river graphic to the background layer
+    pub fn draw(&self, batch: &mut DrawBatch, theme: &Theme) {
+        batch.add_rectangle(&self.form, theme.river());
     }
 }
 
@@ -95,16 +107,16 @@ impl Cubbie {
                 END - 2.0 * SQUARE_SIZE,
                 2.0 * SQUARE_SIZE,
                 2.0 * SQUARE_SIZE,
-                RIVER,
             ),
             is_occupied: false,
+            owner: None,
         }
     }
 
-    /// Draws the cubbie graphic on screen
-    pub fn draw(&mut self, ctx: &mut Context) -> GameResult<()> {
-        self.form.draw(ctx)?;
-        Ok(())
+    /// Adds the cubbie graphic to the background layer
+    pub fn draw(&self, batch: &mut DrawBatch, theme: &Theme) {
+        batch.add_rectangle(&self.form, theme.river());
+        theme.mark_platform(batch, &self.form);
     }
 }
 
@@ -129,18 +141,37 @@ impl Cubbies {
         cubbies
     }
 
-    /// Draws each cubbie in the vector and manages which
-    /// ones are occupied with a crab sprite
-    pub fn draw(&mut self, ctx: &mut Context) -> GameResult<()> {
-        for cubbie in &mut self.cubbies {
-            cubbie.draw(ctx)?;
+    /// Adds each cubbie in the vector to the background layer
+    pub fn draw(&self, batch: &mut DrawBatch, theme: &Theme) {
+        for cubbie in &self.cubbies {
+            cubbie.draw(batch, theme);
+        }
+    }
+
+    /// Draws a crab sprite in each occupied cubbie. These go
+    /// over the background, so it has to be drawn first.
+    pub fn draw_crabs(&self, ctx: &mut Context, assets: &Assets, theme: &Theme) -> GameResult<()> {
+        for cubbie in &self.cubbies {
             if cubbie.is_occupied == true {
-                let image_cubbie_crab = graphics::Image::new(ctx, "/tiny_crab.png")?;
                 let dest_point = graphics::Point2::new(
                     cubbie.form.x + SQUARE_SIZE / 2.0,
                     cubbie.form.y + SQUARE_SIZE / 2.0,
                 );
-                graphics::draw(ctx, &image_cubbie_crab, dest_point, 0.0)?;
+                // Show whose crab made it in when two are on the board
+                let tint = if cubbie.owner == Some(2) {
+                    theme.player_two()
+                } else {
+                    graphics::WHITE
+                };
+                graphics::draw_ex(
+                    ctx,
+                    &assets.small_crab,
+                    DrawParam {
+                        dest: dest_point,
+                        color: Some(tint),
+                        ..Default::default()
+                    },
+                )?;
             }
         }
         Ok(())
@@ -159,62 +190,164 @@ impl Cubbies {
         self.filled_cubbies -= 1;
     }
 
+    /// Records which player's crab filled a cubbie
+    pub fn set_owner(&mut self, i: usize, player: u32) {
+        self.cubbies[i].owner = Some(player);
+    }
+
+    /// Returns how many cubbies a player's crab has filled
+    pub fn get_claimed(&self, player: u32) -> u32 {
+        self.cubbies
+            .iter()
+            .filter(|cubbie| cubbie.owner == Some(player))
+            .count() as u32
+    }
+
+    /// Returns the middle of a cubbie on screen
+    pub fn get_center(&self, i: usize) -> (f32, f32) {
+        let form = &self.cubbies[i].form;
+        (form.x + form.w / 2.0, form.y + form.h / 2.0)
+    }
+
     /// Returns the number of occupied cubbies
     pub fn get_filled_cubbies(&mut self) -> u32 {
         self.filled_cubbies
     }
+
+    /// Returns whether each cubbie is occupied, in order from left to right
+    pub fn get_state(&self) -> Vec<bool> {
+        self.cubbies.iter().map(|cubbie| cubbie.is_occupied).collect()
+    }
+
+    /// Rebuilds the cubbies from a saved list of occupied flags
+    pub fn from_state(occupied: &[bool]) -> Cubbies {
+        let mut cubbies = Cubbies::construct();
+        for (i, is_occupied) in occupied.iter().enumerate() {
+            if *is_occupied && i < cubbies.cubbies.len() {
+                cubbies.set_is_occupied(i);
+            }
+        }
+        cubbies
+    }
 }
 
 /// Implements the game start menu
 impl Menu {
     /// Draws the start menu graphics
-    pub fn draw(&mut self, ctx: &mut Context, selection: u32) -> GameResult<()> {
+    pub fn draw(
+        &mut self,
+        ctx: &mut Context,
+        assets: &mut Assets,
+        menu: &MenuList<MenuChoice>,
+    ) -> GameResult<()> {
         // Draw Crabber name upper-middle
-        let game_name = format! {"CRABBER"};
-        let font = graphics::Font::new(ctx, "/game_over.ttf", 56).unwrap();
-        let name_text = graphics::Text::new(ctx, &game_name, &font)?;
-        let horizontal: f32 = WIN_W as f32 / 2.0 - name_text.width() as f32 / 2.0;
         let vertical: f32 = WIN_H as f32 / 2.0 + SQUARE_SIZE * 1.0;
-        let dest_point = graphics::Point2::new(horizontal, vertical);
-        graphics::draw(ctx, &name_text, dest_point, 0.0)?;
+        assets.draw_centered(ctx, FontSize::Title, "CRABBER", vertical)?;
 
         // Draw the crab logo sprite
-        let image_big_crab = graphics::Image::new(ctx, "/crab.png")?;
-        let horizontal_crab: f32 = WIN_W as f32 / 2.0 - image_big_crab.width() as f32 / 2.0;
+        let horizontal_crab: f32 = WIN_W as f32 / 2.0 - assets.big_crab.width() as f32 / 2.0;
         let vertical_crab: f32 = vertical - SQUARE_SIZE * 8.0;
         let dest_point = graphics::Point2::new(horizontal_crab, vertical_crab);
-        graphics::draw(ctx, &image_big_crab, dest_point, 0.0)?;
-
-        // Draw Start option
-        let start = format! {"Start"};
-        let font_start = graphics::Font::new(ctx, "/game_over.ttf", 20).unwrap();
-        let start_text = graphics::Text::new(ctx, &start, &font_start)?;
-        let horizontal2: f32 = WIN_W as f32 / 2.0 - start_text.width() as f32 / 2.0;
-        let vertical2: f32 = WIN_H as f32 / 2.0 + SQUARE_SIZE * 5.0;
-        let dest_point = graphics::Point2::new(horizontal2, vertical2);
-        graphics::draw(ctx, &start_text, dest_point, 0.0)?;
-
-        // Draw Scores option
-        let scores = format! {"Scores"};
-        let font_score = graphics::Font::new(ctx, "/game_over.ttf", 20).unwrap();
-        let score_text = graphics::Text::new(ctx, &scores, &font_score)?;
-        let horizontal3: f32 = WIN_W as f32 / 2.0 - score_text.width() as f32 / 2.0;
-        let vertical3: f32 = vertical2 + SQUARE_SIZE;
-        let dest_point = graphics::Point2::new(horizontal3, vertical3);
-        graphics::draw(ctx, &score_text, dest_point, 0.0)?;
-
-        if selection == 0 {
-            let image_small_crab = graphics::Image::new(ctx, "/tiny_crab.png")?;
-            let horizontal_small_crab: f32 = horizontal2 - 30.0;
-            let dest_point = graphics::Point2::new(horizontal_small_crab, vertical2 + 10.0);
-            graphics::draw(ctx, &image_small_crab, dest_point, 0.0)?;
-        } else if selection == 1 {
-            let image_small_crab = graphics::Image::new(ctx, "/tiny_crab.png")?;
-            let horizontal_small_crab: f32 = horizontal3 - 30.0;
-            let dest_point = graphics::Point2::new(horizontal_small_crab, vertical3 + 10.0);
-            graphics::draw(ctx, &image_small_crab, dest_point, 0.0)?;
+        graphics::draw(ctx, &assets.big_crab, dest_point, 0.0)?;
+
+        // Draw the menu items below the name
+        menu.draw_at(ctx, assets, WIN_H as f32 / 2.0 + SQUARE_SIZE * 3.0)
+    }
+}
+
+/// Implements the pause menu
+impl PauseMenu {
+    /// Draws the pause menu over whatever is already on screen
+    pub fn draw(
+        &mut self,
+        ctx: &mut Context,
+        assets: &mut Assets,
+        menu: &MenuList<MenuChoice>,
+    ) -> GameResult<()> {
+        draw_overlay(ctx)?;
+        menu.draw(ctx, assets)
+    }
+}
+
+/// Implements the options screen
+impl OptionsMenu {
+    /// Draws the options screen with a hint about rebinding along the bottom
+    pub fn draw(
+        &mut self,
+        ctx: &mut Context,
+        assets: &mut Assets,
+        menu: &MenuList<MenuChoice>,
+    ) -> GameResult<()> {
+        draw_overlay(ctx)?;
+        menu.draw(ctx, assets)?;
+
+        // Explain how to rebind along the bottom
+        let hint = "Confirm on a key to add one, Backspace to reset it";
+        assets.draw_centered(ctx, FontSize::Small, hint, WIN_H as f32 - SQUARE_SIZE)
+    }
+}
+
+/// Implements the online lobby
+impl Lobby {
+    /// Draws what the game is waiting for and how to stop waiting
+    pub fn draw(&mut self, ctx: &mut Context, assets: &mut Assets, message: &str) -> GameResult<()> {
+        assets.draw_centered(ctx, FontSize::Large, "Online", SQUARE_SIZE * 3.0)?;
+        assets.draw_centered(ctx, FontSize::Menu, message, WIN_H as f32 / 2.0)?;
+        assets.draw_centered(
+            ctx,
+            FontSize::Menu,
+            "Press Pause to go back",
+            WIN_H as f32 / 2.0 + SQUARE_SIZE * 2.0,
+        )
+    }
+}
+
+/// Implements the high score table
+impl HighScores {
+    /// Draws the scores under a title, highest first
+    pub fn draw(
+        &mut self,
+        ctx: &mut Context,
+        assets: &mut Assets,
+        title: &str,
+        entries: &[ScoreEntry],
+    ) -> GameResult<()> {
+        assets.draw_centered(ctx, FontSize::Large, title, SQUARE_SIZE)?;
+
+        let mut rows: Vec<String> = entries
+            .iter()
+            .enumerate()
+            .map(|(i, entry)| {
+                format!(
+                    "{}. {}  {}  (level {})",
+                    i + 1,
+                    entry.name,
+                    entry.score,
+                    entry.level
+                )
+            })
+            .collect();
+        if rows.is_empty() {
+            rows.push("No scores yet".to_string());
+        }
+        for (i, row) in rows.iter().enumerate() {
+            assets.draw_centered(ctx, FontSize::Menu, row, SQUARE_SIZE * (3.5 + i as f32))?;
         }
 
-        Ok(())
+        assets.draw_centered(
+            ctx,
+            FontSize::Menu,
+            "Press Pause to go back",
+            WIN_H as f32 - SQUARE_SIZE * 1.5,
+        )
     }
 }
+
+/// Darkens everything already drawn so a menu stands out on top of it
+fn draw_overlay(ctx: &mut Context) -> GameResult<()> {
+    graphics::set_color(ctx, Color::new(0.0, 0.0, 0.0, 0.7))?;
+    let screen = graphics::Rect::new(0.0, 0.0, WIN_W as f32, WIN_H as f32);
+    graphics::rectangle(ctx, DrawMode::Fill, screen)?;
+    graphics::set_color(ctx, graphics::WHITE)?;
+    Ok(())
+}
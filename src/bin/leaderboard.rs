@@ -0,0 +1,54 @@
+/*
+Copyright (c) 2018 Matt Carnovale, Julie Rutherford-Fields, Joshua Sander
+This work is available under the "MIT License”.
+Please see the file LICENSE in this distribution
+for license terms.
+*/
+
+extern crate crabber;
+
+use crabber::constants::{LEADERBOARD_FILE, LEADERBOARD_PORT};
+use crabber::leaderboard::{self, Leaderboard};
+use std::env;
+use std::fs::File;
+use std::net::TcpListener;
+
+/// Reads the scores kept from earlier runs, starting
+/// a new leaderboard if there are none yet
+fn load(path: &str) -> Leaderboard {
+    match File::open(path) {
+        Ok(file) => Leaderboard::read(file).unwrap_or_else(|e| {
+            println!("Could not read {}, starting a new leaderboard: {}", path, e);
+            Leaderboard::new()
+        }),
+        Err(_) => Leaderboard::new(),
+    }
+}
+
+/// Writes the scores out so they survive a restart
+fn save(path: &str, leaderboard: &Leaderboard) {
+    let written = File::create(path).and_then(|file| leaderboard.write(file));
+    if let Err(e) = written {
+        println!("Could not save {}: {}", path, e);
+    }
+}
+
+/// Serves the leaderboard over HTTP on localhost, one request at a time.
+/// Takes the file to keep scores in as an optional argument.
+pub fn main() {
+    let path = env::args()
+        .nth(1)
+        .unwrap_or_else(|| LEADERBOARD_FILE.to_string());
+    let mut leaderboard = load(&path);
+    let listener = TcpListener::bind(("127.0.0.1", LEADERBOARD_PORT)).unwrap();
+    println!("Leaderboard listening on 127.0.0.1:{}", LEADERBOARD_PORT);
+
+    for stream in listener.incoming() {
+        let served = stream.and_then(|stream| leaderboard::serve(&mut leaderboard, stream));
+        match served {
+            Ok(true) => save(&path, &leaderboard),
+            Ok(false) => {}
+            Err(e) => println!("Could not answer request: {}", e),
+        }
+    }
+}
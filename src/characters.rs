@@ -5,22 +5,114 @@ Please see the file LICENSE in this distribution
 for license terms.
 */
 
+use assets::Assets;
 use constants::{END, LIVES, SQUARE_SIZE, WIN_H, WIN_W};
 
+use controls::Action;
+use events::GameEvent;
+use ggez::graphics::Color;
 use ggez::{Context, GameResult};
-use sprites::CrabSprite;
+use sprites::{CrabSprite, Facing, Rectangle};
+use std::mem;
+
+/// The ways the crab can lose a life
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum DeathCause {
+    /// Hit by a car or truck
+    Vehicle,
+    /// Fell into the river
+    Drowned,
+    /// Rode a log or turtle off the edge of the screen
+    CarriedOffscreen,
+    /// Hopped into the wall between cubbies
+    CubbyWall,
+    /// Ran out of time
+    Timeout,
+}
+
+impl DeathCause {
+    /// Returns the message shown onscreen when the crab dies this way
+    pub fn message(&self) -> &'static str {
+        match *self {
+            DeathCause::Vehicle => "SPLAT!",
+            DeathCause::Drowned => "SPLASH!",
+            DeathCause::CarriedOffscreen => "SWEPT AWAY!",
+            DeathCause::CubbyWall => "BONK!",
+            DeathCause::Timeout => "TIME UP!",
+        }
+    }
+}
+
+/// Running totals of how many lives were lost to each cause
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct DeathCounts {
+    pub vehicle: u32,
+    pub drowned: u32,
+    pub carried_offscreen: u32,
+    pub cubby_wall: u32,
+    pub timeout: u32,
+}
+
+impl DeathCounts {
+    /// Counts one more death from the given cause
+    pub fn add(&mut self, cause: DeathCause) {
+        match cause {
+            DeathCause::Vehicle => self.vehicle += 1,
+            DeathCause::Drowned => self.drowned += 1,
+            DeathCause::CarriedOffscreen => self.carried_offscreen += 1,
+            DeathCause::CubbyWall => self.cubby_wall += 1,
+            DeathCause::Timeout => self.timeout += 1,
+        }
+    }
+
+    /// Returns the number of deaths from the given cause
+    pub fn get(&self, cause: DeathCause) -> u32 {
+        match cause {
+            DeathCause::Vehicle => self.vehicle,
+            DeathCause::Drowned => self.drowned,
+            DeathCause::CarriedOffscreen => self.carried_offscreen,
+            DeathCause::CubbyWall => self.cubby_wall,
+            DeathCause::Timeout => self.timeout,
+        }
+    }
+
+    /// Returns the number of deaths from every cause
+    pub fn total(&self) -> u32 {
+        self.vehicle + self.drowned + self.carried_offscreen + self.cubby_wall + self.timeout
+    }
+}
 
 /// Represents the crab / player and associated status
 pub struct Crab {
     form: CrabSprite,
     win_w: f32,
     win_h: f32,
+    start_x: f32,
     lives: i32,
     life_lost: bool,
+    invincible: bool,
+    last_death: Option<DeathCause>,
+    deaths: DeathCounts,
     score: isize,
     speed: f32,
     direction: bool,
     progress: f32,
+    events: Vec<GameEvent>,
+}
+
+/// Everything needed to put the crab back where it was,
+/// used when saving and resuming a game.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct CrabState {
+    pub x: f32,
+    pub y: f32,
+    pub lives: i32,
+    pub score: isize,
+    pub speed: f32,
+    pub direction: bool,
+    pub progress: f32,
+    #[serde(default)]
+    pub deaths: DeathCounts,
 }
 
 /// Implements the crab.
@@ -38,12 +130,17 @@ impl Crab {
             ),
             win_w: WIN_W as f32,
             win_h: WIN_H as f32,
+            start_x: w as f32 / 2.0,
             lives: LIVES,
             life_lost: false,
+            invincible: false,
+            last_death: None,
+            deaths: DeathCounts::default(),
             score: 0,
             speed: 0.0,
             direction: false,
             progress: 0.0,
+            events: vec![],
         }
     }
 
@@ -52,13 +149,13 @@ impl Crab {
     pub fn update(&mut self) {
         if self.direction {
             if self.get_right_edge() >= WIN_W as f32 + self.speed {
-                self.lose_life();
+                self.lose_life(DeathCause::CarriedOffscreen);
             } else {
                 self.form.x = self.form.x + self.speed;
             }
         } else {
             if self.form.x <= 0 as f32 {
-                self.lose_life();
+                self.lose_life(DeathCause::CarriedOffscreen);
             }
             {
                 self.form.x = self.form.x - self.speed;
@@ -67,8 +164,17 @@ impl Crab {
     }
 
     /// Draws crab graphic onscreen
-    pub fn draw(&mut self, ctx: &mut Context) -> GameResult<()> {
-        self.form.draw(ctx)?;
+    pub fn draw(&mut self, ctx: &mut Context, assets: &Assets) -> GameResult<()> {
+        self.form.draw(ctx, &assets.small_crab)?;
+        Ok(())
+    }
+
+    /// Draws one frame of the death animation for the last cause of death.
+    /// Progress runs from 0.0 at the moment of death to 1.0 at respawn.
+    pub fn draw_death(&mut self, ctx: &mut Context, assets: &Assets, progress: f32) -> GameResult<()> {
+        if let Some(cause) = self.last_death {
+            self.form.draw_death(ctx, &assets.small_crab, cause, progress)?;
+        }
         Ok(())
     }
 
@@ -92,47 +198,111 @@ impl Crab {
         return self.form.y - self.form.h;
     }
 
+    /// Returns the area collisions are checked against, between the edges above
+    pub fn get_hitbox(&self) -> Rectangle {
+        Rectangle::construct(self.form.x, self.form.y - self.form.h, self.form.w, self.form.h)
+    }
+
     /// Updates the y coordinate of the crab to move it up
     pub fn move_up(&mut self) {
+        self.form.facing = Facing::Up;
         if self.form.y - SQUARE_SIZE + 1.0 > 0.0 {
-            self.form.y -= SQUARE_SIZE;
+            self.hop_by(0.0, -SQUARE_SIZE);
             self.update_progress();
         }
     }
 
     /// Updates the y coordinate of the crab to move it down
     pub fn move_down(&mut self) {
+        self.form.facing = Facing::Down;
         if self.form.y + SQUARE_SIZE < self.win_h {
-            self.form.y += SQUARE_SIZE;
+            self.hop_by(0.0, SQUARE_SIZE);
         }
     }
 
     /// Updates the x coordinate of the crab to move it right
     pub fn move_right(&mut self) {
+        self.form.facing = Facing::Right;
         if self.form.x + SQUARE_SIZE - 1.0 < self.win_w - SQUARE_SIZE {
-            self.form.x += SQUARE_SIZE;
+            self.hop_by(SQUARE_SIZE, 0.0);
         }
     }
 
     /// Updates the x coordinate of the crab to move it left
     pub fn move_left(&mut self) {
+        self.form.facing = Facing::Left;
         if self.form.x - SQUARE_SIZE + 1.0 > 0.0 {
-            self.form.x -= SQUARE_SIZE;
+            self.hop_by(-SQUARE_SIZE, 0.0);
+        }
+    }
+
+    /// Moves the crab straight to the next square, which is what collisions
+    /// are checked against, and starts the hop there being drawn
+    fn hop_by(&mut self, dx: f32, dy: f32) {
+        let (from_x, from_y) = (self.form.x, self.form.y);
+        self.form.x += dx;
+        self.form.y += dy;
+        self.form.start_hop(from_x, from_y);
+        self.events.push(GameEvent::Hopped);
+    }
+
+    /// Moves any hop being drawn on by a frame
+    pub fn animate(&mut self) {
+        self.form.animate();
+    }
+
+    /// Returns the way the crab is facing
+    pub fn get_facing(&self) -> Facing {
+        self.form.facing
+    }
+
+    /// Hops the crab in the direction of a movement action, other actions are ignored
+    pub fn hop(&mut self, action: Action) {
+        match action {
+            Action::Up => self.move_up(),
+            Action::Down => self.move_down(),
+            Action::Left => self.move_left(),
+            Action::Right => self.move_right(),
+            Action::Confirm | Action::Pause => {}
         }
     }
 
     /// Blocks a crab from entering an occupied cubbie
     pub fn occupied_cubbie_override(&mut self) {
         self.form.y = END;
+        self.form.stop_hop();
     }
 
-    /// Decrements the crabs remaining lives
-    pub fn lose_life(&mut self) {
+    /// Decrements the crabs remaining lives and records what killed it.
+    /// A crab can only die once per respawn.
+    pub fn lose_life(&mut self, cause: DeathCause) {
+        if self.life_lost || self.invincible {
+            return;
+        }
         self.lives = self.lives - 1;
         self.life_lost = true;
+        self.last_death = Some(cause);
+        self.deaths.add(cause);
+        self.events.push(GameEvent::Died(cause));
         self.reset_progress();
     }
 
+    /// Sets whether the crab can die, for testing the game. An invincible
+    /// crab is still carried by logs and turtles.
+    pub fn set_invincible(&mut self, invincible: bool) {
+        self.invincible = invincible;
+    }
+
+    /// Returns the cause of the most recent death, if any
+    pub fn get_last_death(&self) -> Option<DeathCause> {
+        self.last_death
+    }
+
+    /// Returns how many lives have been lost to each cause
+    pub fn get_death_counts(&self) -> &DeathCounts {
+        &self.deaths
+    }
+
     /// Returns the true if life lost, false if not
     pub fn get_life_lost(&mut self) -> bool {
         return self.life_lost;
@@ -143,6 +313,13 @@ impl Crab {
         self.life_lost = false;
     }
 
+    /// Brings a crab that died back to life at the start
+    pub fn respawn(&mut self) {
+        self.set_life_lost();
+        self.restart_x();
+        self.restart_y();
+    }
+
     /// Returns the number of remaining lives
     pub fn get_lives(&mut self) -> i32 {
         return self.lives;
@@ -155,12 +332,26 @@ impl Crab {
 
     /// Sets the x position of crab upon restart
     pub fn restart_x(&mut self) {
-        self.form.x = self.win_w / 2.0;
+        self.form.x = self.start_x;
+    }
+
+    /// Changes the column the crab starts in and moves it there,
+    /// so that two crabs don't start on top of each other
+    pub fn set_start_x(&mut self, x: f32) {
+        self.start_x = x;
+        self.restart_x();
+    }
+
+    /// Sets the color the crab sprite is tinted with
+    pub fn set_tint(&mut self, tint: Color) {
+        self.form.tint = tint;
     }
 
     /// Sets the y position of crab upon restart
     pub fn restart_y(&mut self) {
         self.form.y = self.win_h - 1.0 * SQUARE_SIZE;
+        self.form.facing = Facing::Up;
+        self.form.stop_hop();
     }
 
     /// Returns current score
@@ -192,15 +383,49 @@ impl Crab {
     /// Also updates the score for every step closer it makes it toward the cubbies
     pub fn update_progress(&mut self) {
         if self.progress < self.form.y {
+            self.events.push(GameEvent::ReachedNewRow);
             self.add_to_score(10);
             self.progress += SQUARE_SIZE;
         }
     }
 
+    /// Hands over every event the crab has emitted since the last call
+    pub fn take_events(&mut self) -> Vec<GameEvent> {
+        mem::replace(&mut self.events, vec![])
+    }
+
     /// Resets the progress value to default
     pub fn reset_progress(&mut self) {
         self.progress = 0.0;
     }
+
+    /// Captures the crab's position, lives, score and progress
+    pub fn get_state(&self) -> CrabState {
+        CrabState {
+            x: self.form.x,
+            y: self.form.y,
+            lives: self.lives,
+            score: self.score,
+            speed: self.speed,
+            direction: self.direction,
+            progress: self.progress,
+            deaths: self.deaths.clone(),
+        }
+    }
+
+    /// Puts the crab back into a previously captured state
+    pub fn set_state(&mut self, state: &CrabState) {
+        self.form.x = state.x;
+        self.form.y = state.y;
+        self.lives = state.lives;
+        self.score = state.score;
+        self.speed = state.speed;
+        self.direction = state.direction;
+        self.progress = state.progress;
+        self.deaths = state.deaths.clone();
+        self.life_lost = false;
+        self.form.stop_hop();
+    }
 }
 
 /// Unit tests for Crab functions.  All paths are tested except for draw
@@ -208,6 +433,7 @@ impl Crab {
 mod tests {
 
     use super::*;
+    use constants::HOP_FRAMES;
 
     #[test]
     fn move_left_succeeds_with_space() {
@@ -284,6 +510,34 @@ mod tests {
         assert_eq!(crab.form.y, starting_y - SQUARE_SIZE);
     }
 
+    #[test]
+    fn hop_moves_the_crab_at_once_and_lands_after_a_few_frames() {
+        let mut crab = Crab::new(WIN_W, WIN_H);
+        let starting_y = crab.form.y;
+
+        crab.move_up();
+
+        // Collisions see the new square straight away
+        assert_eq!(crab.form.y, starting_y - SQUARE_SIZE);
+        assert!(crab.form.is_hopping());
+        for _ in 0..HOP_FRAMES {
+            crab.animate();
+        }
+        assert!(!crab.form.is_hopping());
+    }
+
+    #[test]
+    fn crab_faces_the_way_it_last_hopped() {
+        let mut crab = Crab::new(WIN_W, WIN_H);
+        assert_eq!(crab.get_facing(), Facing::Up);
+
+        crab.move_left();
+        assert_eq!(crab.get_facing(), Facing::Left);
+
+        crab.restart_y();
+        assert_eq!(crab.get_facing(), Facing::Up);
+    }
+
     #[test]
     fn move_up_fails_without_space() {
         let mut crab = Crab::new(WIN_W, 0);
@@ -300,7 +554,7 @@ mod tests {
         let updated_life = LIVES - 1;
         let life_lost = true;
 
-        crab.lose_life();
+        crab.lose_life(DeathCause::Vehicle);
 
         assert_eq!(crab.get_lives(), updated_life);
         assert_eq!(crab.get_life_lost(), life_lost);
@@ -313,10 +567,64 @@ mod tests {
         let life_lost_after = true;
 
         assert_eq!(crab.get_life_lost(), life_lost_before);
-        crab.lose_life();
+        crab.lose_life(DeathCause::Vehicle);
         assert_eq!(crab.get_life_lost(), life_lost_after);
     }
 
+    #[test]
+    fn lose_life_counts_cause() {
+        let mut crab = Crab::new(WIN_W, WIN_H);
+
+        crab.lose_life(DeathCause::Drowned);
+
+        assert_eq!(crab.get_last_death(), Some(DeathCause::Drowned));
+        assert_eq!(crab.get_death_counts().get(DeathCause::Drowned), 1);
+        assert_eq!(crab.get_death_counts().get(DeathCause::Vehicle), 0);
+        assert_eq!(crab.get_death_counts().total(), 1);
+    }
+
+    #[test]
+    fn lose_life_only_once_per_respawn() {
+        let mut crab = Crab::new(WIN_W, WIN_H);
+
+        crab.lose_life(DeathCause::Drowned);
+        crab.lose_life(DeathCause::CarriedOffscreen);
+
+        assert_eq!(crab.get_lives(), LIVES - 1);
+        assert_eq!(crab.get_last_death(), Some(DeathCause::Drowned));
+        assert_eq!(crab.get_death_counts().total(), 1);
+    }
+
+    #[test]
+    fn moves_emit_hopped_events() {
+        let mut crab = Crab::new(WIN_W, WIN_H);
+
+        crab.move_left();
+        crab.move_up();
+        crab.move_down();
+
+        let events = crab.take_events();
+        assert_eq!(
+            events,
+            vec![
+                GameEvent::Hopped,
+                GameEvent::Hopped,
+                GameEvent::ReachedNewRow,
+                GameEvent::Hopped,
+            ]
+        );
+        assert!(crab.take_events().is_empty());
+    }
+
+    #[test]
+    fn blocked_move_emits_nothing() {
+        let mut crab = Crab::new(WIN_W, WIN_H);
+
+        crab.move_down();
+
+        assert!(crab.take_events().is_empty());
+    }
+
     #[test]
     fn test_set_life_lost() {
         let mut crab = Crab::new(WIN_W, WIN_H);
@@ -358,4 +666,52 @@ mod tests {
         crab.restart_y();
         assert_eq!(crab.form.y, starting_y);
     }
+
+    #[test]
+    fn test_set_state_restores_get_state() {
+        let mut crab = Crab::new(WIN_W, WIN_H);
+        crab.move_up();
+        crab.move_left();
+        crab.add_to_score(500);
+        crab.lose_life(DeathCause::Vehicle);
+        let state = crab.get_state();
+
+        let mut restored = Crab::new(WIN_W, WIN_H);
+        restored.set_state(&state);
+
+        assert_eq!(restored.form.x, crab.form.x);
+        assert_eq!(restored.form.y, crab.form.y);
+        assert_eq!(restored.get_lives(), crab.get_lives());
+        assert_eq!(restored.get_score(), crab.get_score());
+        assert_eq!(restored.get_life_lost(), false);
+    }
+
+    #[test]
+    fn crab_saved_after_respawning_comes_back_alive_at_the_start() {
+        let mut crab = Crab::new(WIN_W, WIN_H);
+        let start = crab.get_state();
+        crab.move_up();
+        crab.move_up();
+        crab.lose_life(DeathCause::Vehicle);
+
+        crab.respawn();
+        let mut restored = Crab::new(WIN_W, WIN_H);
+        restored.set_state(&crab.get_state());
+
+        assert_eq!(restored.form.x, start.x);
+        assert_eq!(restored.form.y, start.y);
+        assert_eq!(restored.get_lives(), LIVES - 1);
+        assert_eq!(restored.get_life_lost(), false);
+    }
+
+    #[test]
+    fn invincible_crab_keeps_its_lives() {
+        let mut crab = Crab::new(WIN_W, WIN_H);
+        crab.set_invincible(true);
+
+        crab.lose_life(DeathCause::Drowned);
+
+        assert_eq!(crab.get_lives(), LIVES);
+        assert_eq!(crab.get_life_lost(), false);
+    }
 }
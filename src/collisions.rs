@@ -0,0 +1,176 @@
+/*
+Copyright (c) 2018 Matt Carnovale, Julie Rutherford-Fields, Joshua Sander
+This work is available under the "MIT License”.
+Please see the file LICENSE in this distribution
+for license terms.
+*/
+
+use background::Cubbies;
+use characters::{Crab, DeathCause};
+use constants::{END, LOG_EDGE_BUFFER, MID_ROW, SQUARE_SIZE, WIN_H};
+use river::RiverLane;
+use traffic::Lane;
+
+/// Checks a crab against the river, the traffic and the walls between
+/// the cubbies, taking a life for the first thing it has run into.
+/// A crab riding a log is carried along with it.
+pub fn check_collisions(crab: &mut Crab, lanes: &mut [Lane], river_lanes: &mut [RiverLane]) {
+    // Check for collisions
+    // with water
+    if crab.get_bottom_edge() <= MID_ROW as f32 * SQUARE_SIZE - SQUARE_SIZE
+        && crab.get_bottom_edge() >= END
+    {
+        let mut collided = true;
+
+        'outerLog: for i in 0..river_lanes.len() {
+            for j in 0..river_lanes[i].river_transports.len() {
+                // Assume the crab is safe
+                let mut inside = true;
+
+                if crab.get_right_edge()
+                    > river_lanes[i].river_transports[j].get_right_edge() + LOG_EDGE_BUFFER
+                {
+                    inside = false;
+                }
+
+                if crab.get_left_edge()
+                    < river_lanes[i].river_transports[j].get_left_edge() - LOG_EDGE_BUFFER
+                {
+                    inside = false;
+                }
+
+                if crab.get_bottom_edge() < river_lanes[i].river_transports[j].get_bottom_edge() {
+                    inside = false;
+                }
+
+                if crab.get_top_edge() > river_lanes[i].river_transports[j].get_top_edge() {
+                    inside = false;
+                }
+
+                //The crab has passed all of the checks for a particular log, meaning it is inside
+                if inside == true {
+                    crab.set_direction(river_lanes[i].river_transports[j].get_direction());
+                    crab.set_speed(river_lanes[i].river_transports[j].get_speed());
+                    collided = false;
+                    break 'outerLog;
+                }
+            }
+        }
+
+        if collided {
+            crab.lose_life(DeathCause::Drowned);
+        }
+
+        //Update the crab's speed
+        crab.update();
+    }
+    // or with vehicles else
+    if crab.get_bottom_edge() > MID_ROW as f32 * SQUARE_SIZE
+        && crab.get_bottom_edge() < WIN_H as f32 - SQUARE_SIZE * 2.0
+    {
+        'outerCar: for i in 0..lanes.len() {
+            for j in 0..lanes[i].vehicles.len() {
+                if crab.get_left_edge() >= lanes[i].vehicles[j].get_right_edge() {
+                    continue;
+                }
+
+                if crab.get_right_edge() <= lanes[i].vehicles[j].get_left_edge() {
+                    continue;
+                }
+
+                if crab.get_bottom_edge() <= lanes[i].vehicles[j].get_top_edge() {
+                    continue;
+                }
+
+                if crab.get_top_edge() >= lanes[i].vehicles[j].get_bottom_edge() {
+                    continue;
+                }
+
+                crab.lose_life(DeathCause::Vehicle);
+                break 'outerCar;
+            }
+        }
+    }
+
+    // Check for collisions with cubbies
+    if crab.get_bottom_edge() < END
+        && crab.get_left_edge() % (SQUARE_SIZE * 4.0) < SQUARE_SIZE * 2.0
+    {
+        crab.lose_life(DeathCause::CubbyWall);
+    }
+}
+
+/// Fills the cubbie a crab has hopped into, scoring for it and sending
+/// the crab back to the start. Returns the index of the cubbie filled.
+/// A crab hopping into an occupied cubbie is pushed back out of it.
+pub fn enter_cubbie(crab: &mut Crab, cubbies: &mut Cubbies) -> Option<usize> {
+    if crab.get_bottom_edge() < END
+        && crab.get_left_edge() % (SQUARE_SIZE * 4.0) >= SQUARE_SIZE * 2.0
+    {
+        let i = (crab.get_left_edge() / (SQUARE_SIZE * 4.0)) as usize;
+        if cubbies.get_is_occupied(i) == false {
+            // Get points for it
+            crab.add_to_score(500);
+            // Set occupied flag to true
+            cubbies.set_is_occupied(i);
+            crab.restart_x();
+            crab.restart_y();
+            return Some(i);
+        } else {
+            crab.occupied_cubbie_override();
+        }
+    }
+    None
+}
+
+/// Unit tests for the cubbie checks.
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use constants::{START, WIN_W};
+
+    /// Puts a crab in the opening of the first cubbie
+    fn crab_at_first_cubbie() -> Crab {
+        let mut crab = Crab::new(WIN_W, START as u32);
+        let mut state = crab.get_state();
+        state.x = SQUARE_SIZE * 2.0;
+        state.y = SQUARE_SIZE;
+        crab.set_state(&state);
+        crab
+    }
+
+    #[test]
+    fn entering_a_cubbie_fills_it() {
+        let mut crab = crab_at_first_cubbie();
+        let mut cubbies = Cubbies::construct();
+
+        assert_eq!(enter_cubbie(&mut crab, &mut cubbies), Some(0));
+        assert!(cubbies.get_is_occupied(0));
+        assert_eq!(crab.get_score(), 500);
+        assert_eq!(crab.get_bottom_edge(), START);
+    }
+
+    #[test]
+    fn occupied_cubbie_is_not_filled_again() {
+        let mut crab = crab_at_first_cubbie();
+        let mut cubbies = Cubbies::construct();
+        cubbies.set_is_occupied(0);
+
+        assert_eq!(enter_cubbie(&mut crab, &mut cubbies), None);
+        assert_eq!(crab.get_score(), 0);
+    }
+
+    #[test]
+    fn claimed_cubbie_counts_for_its_owner_only() {
+        let mut cubbies = Cubbies::construct();
+        let i = enter_cubbie(&mut crab_at_first_cubbie(), &mut cubbies).unwrap();
+        cubbies.set_owner(i, 2);
+
+        // The other crab is pushed back out of it
+        let mut rival = crab_at_first_cubbie();
+        assert_eq!(enter_cubbie(&mut rival, &mut cubbies), None);
+        assert_eq!(cubbies.get_claimed(1), 0);
+        assert_eq!(cubbies.get_claimed(2), 1);
+    }
+}
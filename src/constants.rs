@@ -5,8 +5,6 @@ Please see the file LICENSE in this distribution
 for license terms.
 */
 
-use ggez::graphics::Color;
-
 /// Square pixel dimensions as basis for all graphics
 pub const SQUARE_SIZE: f32 = 25.0;
 
@@ -35,48 +33,19 @@ pub const NUM_LOG: u32 = NUM_ROW - MID_ROW - 3;
 /// Number of cubbies to be drawn to fit the window
 pub const CUB_NUM: u32 = (NUM_COL / 2 - 1) / 2;
 
-// Colors
-/// Grass RGBA value
-pub const GRASS: Color = Color {
-    r: 0.0,
-    g: 0.7,
-    b: 0.23,
-    a: 1.0,
-};
-/// Road RGBA value
-pub const ROAD: Color = Color {
-    r: 0.1,
-    g: 0.2,
-    b: 0.3,
-    a: 1.0,
-};
-/// River RGBA value
-pub const RIVER: Color = Color {
-    r: 0.3,
-    g: 0.3,
-    b: 1.0,
-    a: 1.0,
-};
-/// Log RGBA value
-pub const LOG: Color = Color {
-    r: 0.6,
-    g: 0.3,
-    b: 0.1,
-    a: 1.0,
-};
-/// Turtle RGBA value
-pub const TURTLE: Color = Color {
-    r: 0.5,
-    g: 0.5,
-    b: 0.5,
-    a: 1.0,
-};
-
 // Crab lives
 /// Default number of crab lives
 pub const LIVES: i32 = 3;
 /// Pixel allowance for crab to land on log
 pub const LOG_EDGE_BUFFER: f32 = 10.0;
+/// Number of frames the board is held still for while a crab's death plays out
+pub const DEATH_ANIMATION_FRAMES: u32 = 60;
+/// Number of frames the board is held still for after a cubby is filled
+pub const CUBBY_HOLD_FRAMES: u32 = 60;
+/// Number of frames a cleared level, won race or game over is announced for
+pub const ANNOUNCEMENT_FRAMES: u32 = 120;
+/// Number of frames the crab takes to hop from one square to the next
+pub const HOP_FRAMES: u32 = 6;
 
 // Traffic nums
 /// Defines the y coordinate of the first lane of road traffic
@@ -108,9 +77,108 @@ pub const LOG_W: f32 = TRUCK_W;
 /// Width of log, to be kept consistent with that of a car
 pub const TURTLE_W: f32 = CAR_W;
 
+// Controllers
+/// How far the stick must be pushed, out of 32767, before it counts
+pub const STICK_DEADZONE: i16 = 8000;
+/// Frames a direction must be held before it starts repeating
+pub const GAMEPAD_REPEAT_DELAY: u32 = 20;
+/// Frames between repeats once a held direction is repeating
+pub const GAMEPAD_REPEAT_INTERVAL: u32 = 8;
+
+// Practice mode
+/// Number of frames of history kept for rewinding, about 10 seconds
+pub const REWIND_FRAMES: usize = 600;
+
+// Two player mode
+/// Frames the "PLAYER n" banner is shown for when turns change
+pub const TURN_BANNER_FRAMES: u32 = 90;
+
+// Co-op mode
+/// How far each crab starts from the middle column when both are on the board
+pub const COOP_START_OFFSET: f32 = SQUARE_SIZE * 2.0;
+
+// Versus mode
+/// Cubbies a player must claim to win a race, over half of them so there's always a winner
+pub const VERSUS_CUBBIES: u32 = CUB_NUM / 2 + 1;
+
+// Online play
+/// Port a hosted game listens on
+pub const NET_PORT: u16 = 7878;
+/// Address a joining game connects to until the player sets their own
+pub const DEFAULT_JOIN_ADDRESS: &str = "127.0.0.1:7878";
+/// How long to keep trying to reach a host before giving up
+pub const NET_CONNECT_TIMEOUT_MS: u64 = 3000;
+/// Frames between an action being taken and it happening on both boards
+pub const NET_INPUT_DELAY: u64 = 3;
+/// Frames spent waiting on the other game before saying so on screen
+pub const NET_STALL_FRAMES: u32 = 30;
+/// Longest message the other game may send, far more than a frame's actions
+pub const NET_MAX_LINE_BYTES: usize = 4 * 1024;
+
+// Spectating
+/// Port a broadcasting game publishes its frames on
+pub const SPECTATE_PORT: u16 = 7879;
+/// Address "Spectate" connects to until the player sets their own
+pub const DEFAULT_SPECTATE_ADDRESS: &str = "127.0.0.1:7879";
+/// Longest frame a spectator reads, with plenty of room for a full board
+pub const SPECTATE_MAX_LINE_BYTES: usize = 256 * 1024;
+
+// Leaderboard
+/// Port the leaderboard server listens on
+pub const LEADERBOARD_PORT: u16 = 7880;
+/// Address scores are sent to until the player sets their own
+pub const DEFAULT_LEADERBOARD_ADDRESS: &str = "127.0.0.1:7880";
+/// Number of scores kept on a high score table
+pub const LEADERBOARD_SIZE: usize = 10;
+/// Largest message body the leaderboard reads, well over an hour-long replay
+pub const MAX_REQUEST_BYTES: usize = 4 * 1024 * 1024;
+/// Largest request line and headers the leaderboard reads before the body
+pub const MAX_HEAD_BYTES: u64 = 8 * 1024;
+/// Most headers the leaderboard reads in one request
+pub const MAX_HEADERS: usize = 32;
+/// Longest the leaderboard server spends reading one request
+pub const REQUEST_DEADLINE_MS: u64 = 10_000;
+/// Name scores are recorded under until the player sets their own
+pub const DEFAULT_PLAYER_NAME: &str = "CRAB";
+/// Longest game a replay is played back for, an hour at 60 frames a second
+pub const MAX_REPLAY_FRAMES: u64 = 60 * 60 * 60;
+
+// Files
+/// Location of the save game within the user's config directory
+pub const SAVE_FILE: &str = "/save.json";
+/// Location of the player's settings within the user's config directory
+pub const SETTINGS_FILE: &str = "/settings.json";
+/// Location of the local high scores within the user's config directory
+pub const HIGH_SCORES_FILE: &str = "/scores.json";
+/// Art for the traffic and river transports within the resources directory
+pub const SPRITE_SHEET: &str = "/sprites.png";
+/// The game's font within the resources directory
+pub const FONT_FILE: &str = "/game_over.ttf";
+/// The crab logo on the title screen within the resources directory
+pub const BIG_CRAB_IMAGE: &str = "/crab.png";
+/// The crab the player moves within the resources directory
+pub const SMALL_CRAB_IMAGE: &str = "/tiny_crab.png";
+/// Directory of theme files within the resources directory
+pub const THEMES_DIR: &str = "/themes";
+/// Theme used until another is picked, and when the picked one can't be read
+pub const DEFAULT_THEME: &str = "classic";
+/// File the leaderboard server keeps its scores in, relative to where it is run
+pub const LEADERBOARD_FILE: &str = "leaderboard.json";
+
+// Drawing
+/// Number of pieces of rendered text kept before starting over
+pub const TEXT_CACHE_SIZE: usize = 256;
+/// Most particles on screen at once
+pub const MAX_PARTICLES: usize = 1024;
+/// Thickness of the stripes and spots patterned over lane objects
+pub const PATTERN_MARK: f32 = 4.0;
+
+// Audio
+/// Volume of music and sound effects before the player changes them
+pub const DEFAULT_VOLUME: f32 = 0.7;
+
 // Dev toggles
-/// Toggles collisions with game obstacles to support
-/// dev and testing of new features
-pub const COLLISIONS_ON: bool = true;
+/// Number of recent frames the debug overlay averages the update time over
+pub const UPDATE_SAMPLES: usize = 60;
 /// Sets the number of cubbies needed to be occupied in order to win
 pub const WINNING_CUBBIES: u32 = 0;
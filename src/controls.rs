@@ -0,0 +1,373 @@
+/*
+Copyright (c) 2018 Matt Carnovale, Julie Rutherford-Fields, Joshua Sander
+This work is available under the "MIT License”.
+Please see the file LICENSE in this distribution
+for license terms.
+*/
+
+use constants::{GAMEPAD_REPEAT_DELAY, GAMEPAD_REPEAT_INTERVAL, STICK_DEADZONE};
+use ggez::event::{Axis, Button, Keycode};
+use std::collections::HashMap;
+
+/// Something the player can ask the game to do,
+/// independent of which key or button asked for it
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum Action {
+    Up,
+    Down,
+    Left,
+    Right,
+    Confirm,
+    Pause,
+}
+
+/// Every action, in the order they are listed on the options screen
+pub const ACTIONS: [Action; 6] = [
+    Action::Up,
+    Action::Down,
+    Action::Left,
+    Action::Right,
+    Action::Confirm,
+    Action::Pause,
+];
+
+impl Action {
+    /// Returns the name shown for the action on the options screen
+    pub fn label(&self) -> &'static str {
+        match *self {
+            Action::Up => "Up",
+            Action::Down => "Down",
+            Action::Left => "Left",
+            Action::Right => "Right",
+            Action::Confirm => "Confirm",
+            Action::Pause => "Pause",
+        }
+    }
+
+    /// Returns the keys bound to the action before the player changes anything
+    pub fn default_keys(&self) -> Vec<Keycode> {
+        match *self {
+            Action::Up => vec![Keycode::Up, Keycode::W],
+            Action::Down => vec![Keycode::Down, Keycode::S],
+            Action::Left => vec![Keycode::Left, Keycode::A],
+            Action::Right => vec![Keycode::Right, Keycode::D],
+            Action::Confirm => vec![Keycode::Return, Keycode::Space],
+            Action::Pause => vec![Keycode::Escape, Keycode::P],
+        }
+    }
+}
+
+/// Maps keys to actions. Each action can be bound to several keys
+/// but each key only ever triggers one action.
+#[derive(Clone, Debug, PartialEq)]
+pub struct KeyMap {
+    bindings: HashMap<Action, Vec<Keycode>>,
+}
+
+impl Default for KeyMap {
+    fn default() -> KeyMap {
+        let mut bindings = HashMap::new();
+        for action in ACTIONS.iter() {
+            bindings.insert(*action, action.default_keys());
+        }
+        KeyMap { bindings }
+    }
+}
+
+impl KeyMap {
+    /// The movement keys for the second crab in co-op, kept clear of the
+    /// first player's defaults. Only movement is bound, the first player
+    /// handles the menus.
+    pub fn player_two() -> KeyMap {
+        let mut bindings = HashMap::new();
+        bindings.insert(Action::Up, vec![Keycode::I]);
+        bindings.insert(Action::Down, vec![Keycode::K]);
+        bindings.insert(Action::Left, vec![Keycode::J]);
+        bindings.insert(Action::Right, vec![Keycode::L]);
+        KeyMap { bindings }
+    }
+
+    /// Builds a key map from the key names kept in the settings file.
+    /// Actions missing from the file keep their default keys and
+    /// names that don't match a key are skipped.
+    pub fn from_names(names: &HashMap<Action, Vec<String>>) -> KeyMap {
+        let mut key_map = KeyMap::default();
+        for (action, keys) in names {
+            let keys = keys.iter()
+                .filter_map(|name| Keycode::from_name(name))
+                .collect();
+            key_map.bindings.insert(*action, keys);
+        }
+        key_map
+    }
+
+    /// Returns the key names to be kept in the settings file
+    pub fn to_names(&self) -> HashMap<Action, Vec<String>> {
+        self.bindings
+            .iter()
+            .map(|(action, keys)| (*action, keys.iter().map(|key| key.name()).collect()))
+            .collect()
+    }
+
+    /// Returns the action a key is bound to, if any
+    pub fn action_for(&self, key: Keycode) -> Option<Action> {
+        ACTIONS
+            .iter()
+            .find(|action| self.keys_for(**action).contains(&key))
+            .cloned()
+    }
+
+    /// Returns every key bound to an action
+    pub fn keys_for(&self, action: Action) -> &[Keycode] {
+        match self.bindings.get(&action) {
+            Some(keys) => keys,
+            None => &[],
+        }
+    }
+
+    /// Adds a key to an action, taking it away from any other action first
+    pub fn bind(&mut self, action: Action, key: Keycode) {
+        for keys in self.bindings.values_mut() {
+            keys.retain(|bound| *bound != key);
+        }
+        self.bindings.entry(action).or_insert_with(Vec::new).push(key);
+    }
+
+    /// Puts an action back to its default keys
+    pub fn reset(&mut self, action: Action) {
+        let defaults = action.default_keys();
+        for keys in self.bindings.values_mut() {
+            keys.retain(|bound| !defaults.contains(bound));
+        }
+        self.bindings.insert(action, defaults);
+    }
+}
+
+/// Returns the action for a controller button. In menus A and Start
+/// both confirm and B goes back, in game Start pauses.
+pub fn button_action(button: Button, in_menu: bool) -> Option<Action> {
+    match button {
+        Button::DPadUp => Some(Action::Up),
+        Button::DPadDown => Some(Action::Down),
+        Button::DPadLeft => Some(Action::Left),
+        Button::DPadRight => Some(Action::Right),
+        Button::A => Some(Action::Confirm),
+        Button::Start if in_menu => Some(Action::Confirm),
+        Button::Start => Some(Action::Pause),
+        Button::B if in_menu => Some(Action::Pause),
+        _ => None,
+    }
+}
+
+/// Turns the D-pad and left stick of a controller into direction actions.
+/// The stick has a deadzone so it doesn't drift, and a direction held on
+/// either one repeats after a short delay the same way a held key does.
+pub struct GamepadInput {
+    stick_x: i16,
+    stick_y: i16,
+    dpad: Option<Action>,
+    held: Option<Action>,
+    held_frames: u32,
+}
+
+impl Default for GamepadInput {
+    fn default() -> GamepadInput {
+        GamepadInput::new()
+    }
+}
+
+impl GamepadInput {
+    /// Creates the input with nothing held
+    pub fn new() -> GamepadInput {
+        GamepadInput {
+            stick_x: 0,
+            stick_y: 0,
+            dpad: None,
+            held: None,
+            held_frames: 0,
+        }
+    }
+
+    /// Records a D-pad button being pressed. Every press fires,
+    /// even one in the direction the stick is already held.
+    pub fn dpad_down(&mut self, action: Action) -> Action {
+        self.dpad = Some(action);
+        self.refresh_held();
+        self.held_frames = 0;
+        action
+    }
+
+    /// Records a D-pad button being released
+    pub fn dpad_up(&mut self, action: Action) {
+        if self.dpad == Some(action) {
+            self.dpad = None;
+        }
+        self.refresh_held();
+    }
+
+    /// Records the left stick moving. Returns an action when the
+    /// stick is pushed into a new direction.
+    pub fn axis(&mut self, axis: Axis, value: i16) -> Option<Action> {
+        match axis {
+            Axis::LeftX => self.stick_x = value,
+            Axis::LeftY => self.stick_y = value,
+            _ => return None,
+        }
+        self.refresh_held()
+    }
+
+    /// Called once per frame, returns the held direction whenever it should repeat
+    pub fn update(&mut self) -> Option<Action> {
+        let held = self.held?;
+        self.held_frames += 1;
+        if self.held_frames >= GAMEPAD_REPEAT_DELAY
+            && (self.held_frames - GAMEPAD_REPEAT_DELAY) % GAMEPAD_REPEAT_INTERVAL == 0
+        {
+            Some(held)
+        } else {
+            None
+        }
+    }
+
+    /// Returns the direction the stick is pushed in, going with
+    /// whichever axis is pushed furthest past the deadzone
+    fn stick_direction(&self) -> Option<Action> {
+        let x = i32::from(self.stick_x);
+        let y = i32::from(self.stick_y);
+        let deadzone = i32::from(STICK_DEADZONE);
+        if x.abs() < deadzone && y.abs() < deadzone {
+            None
+        } else if x.abs() > y.abs() {
+            Some(if x > 0 { Action::Right } else { Action::Left })
+        } else {
+            // Pushing the stick up gives a negative value
+            Some(if y > 0 { Action::Down } else { Action::Up })
+        }
+    }
+
+    /// Works out which direction is now held, the D-pad winning over
+    /// the stick. Returns it if it has just changed so it fires at once.
+    fn refresh_held(&mut self) -> Option<Action> {
+        let held = self.dpad.or_else(|| self.stick_direction());
+        if held == self.held {
+            return None;
+        }
+        self.held = held;
+        self.held_frames = 0;
+        held
+    }
+}
+
+/// Unit tests for KeyMap and GamepadInput.
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    #[test]
+    fn default_map_has_arrows_and_wasd() {
+        let key_map = KeyMap::default();
+
+        assert_eq!(key_map.action_for(Keycode::Up), Some(Action::Up));
+        assert_eq!(key_map.action_for(Keycode::W), Some(Action::Up));
+        assert_eq!(key_map.action_for(Keycode::A), Some(Action::Left));
+        assert_eq!(key_map.action_for(Keycode::Return), Some(Action::Confirm));
+        assert_eq!(key_map.action_for(Keycode::Escape), Some(Action::Pause));
+        assert_eq!(key_map.action_for(Keycode::R), None);
+    }
+
+    #[test]
+    fn bind_adds_key_alongside_existing_ones() {
+        let mut key_map = KeyMap::default();
+
+        key_map.bind(Action::Up, Keycode::I);
+
+        assert_eq!(key_map.keys_for(Action::Up), &[Keycode::Up, Keycode::W, Keycode::I]);
+    }
+
+    #[test]
+    fn bind_takes_key_from_other_action() {
+        let mut key_map = KeyMap::default();
+
+        key_map.bind(Action::Up, Keycode::S);
+
+        assert_eq!(key_map.action_for(Keycode::S), Some(Action::Up));
+        assert_eq!(key_map.keys_for(Action::Down), &[Keycode::Down]);
+    }
+
+    #[test]
+    fn reset_restores_defaults() {
+        let mut key_map = KeyMap::default();
+        key_map.bind(Action::Up, Keycode::S);
+
+        key_map.reset(Action::Down);
+
+        assert_eq!(key_map.keys_for(Action::Down), &[Keycode::Down, Keycode::S]);
+        assert_eq!(key_map.keys_for(Action::Up), &[Keycode::Up, Keycode::W]);
+    }
+
+    #[test]
+    fn player_two_keys_are_free_in_the_default_map() {
+        let key_map = KeyMap::default();
+        let player_two = KeyMap::player_two();
+
+        for key in [Keycode::I, Keycode::J, Keycode::K, Keycode::L].iter() {
+            assert!(player_two.action_for(*key).is_some());
+            assert_eq!(key_map.action_for(*key), None);
+        }
+    }
+
+    #[test]
+    fn start_confirms_in_menus_and_pauses_in_game() {
+        assert_eq!(button_action(Button::Start, true), Some(Action::Confirm));
+        assert_eq!(button_action(Button::Start, false), Some(Action::Pause));
+        assert_eq!(button_action(Button::B, false), None);
+    }
+
+    #[test]
+    fn stick_inside_deadzone_does_nothing() {
+        let mut gamepad = GamepadInput::new();
+
+        assert_eq!(gamepad.axis(Axis::LeftX, STICK_DEADZONE - 1), None);
+        assert_eq!(gamepad.update(), None);
+    }
+
+    #[test]
+    fn stick_fires_once_then_repeats() {
+        let mut gamepad = GamepadInput::new();
+
+        assert_eq!(gamepad.axis(Axis::LeftY, -32000), Some(Action::Up));
+        // Moving further in the same direction doesn't fire again
+        assert_eq!(gamepad.axis(Axis::LeftY, -32768), None);
+
+        let mut repeats = 0;
+        for _ in 0..GAMEPAD_REPEAT_DELAY + GAMEPAD_REPEAT_INTERVAL {
+            if gamepad.update() == Some(Action::Up) {
+                repeats += 1;
+            }
+        }
+        assert_eq!(repeats, 2);
+
+        assert_eq!(gamepad.axis(Axis::LeftY, 0), None);
+        assert_eq!(gamepad.update(), None);
+    }
+
+    #[test]
+    fn dpad_wins_over_stick() {
+        let mut gamepad = GamepadInput::new();
+        gamepad.axis(Axis::LeftX, 32000);
+
+        gamepad.dpad_down(Action::Up);
+        for _ in 0..GAMEPAD_REPEAT_DELAY - 1 {
+            gamepad.update();
+        }
+        assert_eq!(gamepad.update(), Some(Action::Up));
+
+        // Letting go of the D-pad falls back to the stick
+        gamepad.dpad_up(Action::Up);
+        for _ in 0..GAMEPAD_REPEAT_DELAY - 1 {
+            gamepad.update();
+        }
+        assert_eq!(gamepad.update(), Some(Action::Right));
+    }
+}
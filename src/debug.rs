@@ -0,0 +1,222 @@
+/*
+Copyright (c) 2018 Matt Carnovale, Julie Rutherford-Fields, Joshua Sander
+This work is available under the "MIT License”.
+Please see the file LICENSE in this distribution
+for license terms.
+*/
+
+use assets::{Assets, FontSize};
+use characters::Crab;
+use constants::{LOG_EDGE_BUFFER, UPDATE_SAMPLES, WIN_W};
+use ggez::graphics::{self, Color, Point2};
+use ggez::{timer, Context, GameResult};
+use river::RiverLane;
+use sprites::{DrawBatch, Rectangle};
+use std::collections::VecDeque;
+use std::time::Duration;
+use traffic::Lane;
+
+/// Outline of every crab
+const CRAB_BOX: Color = Color {
+    r: 0.0,
+    g: 1.0,
+    b: 0.0,
+    a: 1.0,
+};
+
+/// Outline of every vehicle, which the crab dies touching
+const HAZARD_BOX: Color = Color {
+    r: 1.0,
+    g: 0.0,
+    b: 0.0,
+    a: 1.0,
+};
+
+/// Outline of every log and turtle
+const PLATFORM_BOX: Color = Color {
+    r: 1.0,
+    g: 1.0,
+    b: 0.0,
+    a: 1.0,
+};
+
+/// Outline of how far past a log or turtle's ends the crab can stand
+const BUFFER_BOX: Color = Color {
+    r: 1.0,
+    g: 1.0,
+    b: 0.0,
+    a: 0.4,
+};
+
+/// Information for working on the game drawn over the board: the
+/// hitboxes collisions are checked against, each lane's traffic and
+/// how fast the game is running. Also holds whether crabs can be hurt.
+pub struct DebugOverlay {
+    visible: bool,
+    invincible: bool,
+    update_times: VecDeque<Duration>,
+    batch: DrawBatch,
+}
+
+impl DebugOverlay {
+    /// Creates a hidden overlay with crabs that can be hurt
+    pub fn new() -> DebugOverlay {
+        DebugOverlay {
+            visible: false,
+            invincible: false,
+            update_times: VecDeque::with_capacity(UPDATE_SAMPLES),
+            batch: DrawBatch::new(None),
+        }
+    }
+
+    /// Shows or hides the overlay
+    pub fn toggle(&mut self) {
+        self.visible = !self.visible;
+    }
+
+    /// Returns true if the overlay is shown
+    pub fn is_visible(&self) -> bool {
+        self.visible
+    }
+
+    /// Turns invincibility on or off
+    pub fn toggle_invincible(&mut self) {
+        self.invincible = !self.invincible;
+    }
+
+    /// Returns true if crabs can't be hurt
+    pub fn is_invincible(&self) -> bool {
+        self.invincible
+    }
+
+    /// Records how long a frame took to update, keeping only the latest few
+    pub fn record_update(&mut self, time: Duration) {
+        if self.update_times.len() >= UPDATE_SAMPLES {
+            self.update_times.pop_front();
+        }
+        self.update_times.push_back(time);
+    }
+
+    /// Returns the average update time over the latest frames in milliseconds
+    pub fn average_update_ms(&self) -> f64 {
+        if self.update_times.is_empty() {
+            return 0.0;
+        }
+        let total: f64 = self.update_times
+            .iter()
+            .map(|time| time.as_secs() as f64 + time.subsec_nanos() as f64 * 1e-9)
+            .sum();
+        total * 1000.0 / self.update_times.len() as f64
+    }
+
+    /// Draws the hitboxes, lane information and timings over the board.
+    /// While hidden only a reminder that crabs are invincible is drawn.
+    pub fn draw(
+        &mut self,
+        ctx: &mut Context,
+        assets: &mut Assets,
+        crabs: &[&Crab],
+        lanes: &[Lane],
+        river_lanes: &[RiverLane],
+    ) -> GameResult<()> {
+        if !self.visible {
+            if self.invincible {
+                assets.draw_centered(ctx, FontSize::Small, "Invincible", 0.0)?;
+            }
+            return Ok(());
+        }
+
+        for crab in crabs {
+            self.batch.add_outline(&crab.get_hitbox(), CRAB_BOX, 1.0);
+        }
+        for lane in lanes {
+            for vehicle in &lane.vehicles {
+                self.batch.add_outline(&vehicle.get_hitbox(), HAZARD_BOX, 1.0);
+            }
+        }
+        for river_lane in river_lanes {
+            for transport in &river_lane.river_transports {
+                let hitbox = transport.get_hitbox();
+                let buffer = Rectangle::construct(
+                    hitbox.x - LOG_EDGE_BUFFER,
+                    hitbox.y,
+                    hitbox.w + 2.0 * LOG_EDGE_BUFFER,
+                    hitbox.h,
+                );
+                self.batch.add_outline(&buffer, BUFFER_BOX, 1.0);
+                self.batch.add_outline(&hitbox, PLATFORM_BOX, 1.0);
+            }
+        }
+        self.batch.draw(ctx)?;
+
+        // Each lane's speed, direction and how many are in it, down the right
+        let traffic = lanes.iter().map(|lane| {
+            lane.get_state()
+                .iter()
+                .map(|vehicle| (vehicle.y, vehicle.speed, vehicle.direction))
+                .collect::<Vec<_>>()
+        });
+        let river = river_lanes.iter().map(|river_lane| {
+            river_lane
+                .get_state()
+                .iter()
+                .map(|transport| (transport.y, transport.speed, transport.direction))
+                .collect::<Vec<_>>()
+        });
+        for objects in traffic.chain(river) {
+            if let Some(&(y, speed, direction)) = objects.first() {
+                let arrow = if direction { ">>" } else { "<<" };
+                let info = format!("{} {:.1} x{}", arrow, speed, objects.len());
+                let text = assets.text(ctx, FontSize::Small, &info)?;
+                let dest_point = Point2::new(WIN_W as f32 - text.width() as f32 - 4.0, y);
+                graphics::draw(ctx, text, dest_point, 0.0)?;
+            }
+        }
+
+        let timings = format!(
+            "FPS {:.0}  update {:.2} ms  invincible {} (F4)",
+            timer::get_fps(ctx),
+            self.average_update_ms(),
+            if self.invincible { "on" } else { "off" }
+        );
+        let text = assets.text(ctx, FontSize::Small, &timings)?;
+        graphics::draw(ctx, text, Point2::new(4.0, 0.0), 0.0)
+    }
+}
+
+impl Default for DebugOverlay {
+    fn default() -> DebugOverlay {
+        DebugOverlay::new()
+    }
+}
+
+/// Unit tests for DebugOverlay.
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use rng::GameRng;
+
+    #[test]
+    fn update_time_averages_the_latest_frames() {
+        let mut debug = DebugOverlay::new();
+        debug.record_update(Duration::from_millis(100));
+        for _ in 0..UPDATE_SAMPLES {
+            debug.record_update(Duration::from_millis(2));
+        }
+
+        assert!((debug.average_update_ms() - 2.0).abs() < 0.001);
+    }
+
+    #[test]
+    fn vehicle_hitbox_lies_between_the_edges_collisions_test() {
+        let lane = Lane::construct(400.0, &mut GameRng::new(7));
+        let vehicle = &lane.vehicles[0];
+        let hitbox = vehicle.get_hitbox();
+
+        assert_eq!(hitbox.x, vehicle.get_left_edge());
+        assert_eq!(hitbox.y, vehicle.get_top_edge());
+        assert_eq!(hitbox.x + hitbox.w, vehicle.get_right_edge());
+        assert_eq!(hitbox.y + hitbox.h, vehicle.get_bottom_edge());
+    }
+}
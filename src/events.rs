@@ -0,0 +1,95 @@
+/*
+Copyright (c) 2018 Matt Carnovale, Julie Rutherford-Fields, Joshua Sander
+This work is available under the "MIT License”.
+Please see the file LICENSE in this distribution
+for license terms.
+*/
+
+use characters::DeathCause;
+use std::collections::VecDeque;
+
+/// Something noteworthy that happened during play. The game logic
+/// only records these; sound, statistics and the like react to them.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum GameEvent {
+    /// The crab hopped one square in any direction
+    Hopped,
+    /// The crab got further up the screen than it has been this life
+    ReachedNewRow,
+    /// The crab lost a life
+    Died(DeathCause),
+    /// The crab made it into the cubbie with this index
+    CubbyFilled(usize),
+    /// Every cubbie has been filled
+    LevelCleared,
+    /// The crab ran out of lives
+    GameOver,
+    /// The crab was given another life. No rule awards one yet.
+    ExtraLife,
+}
+
+/// Implemented by anything that wants to be told about game events
+pub trait GameEventListener {
+    /// Called once for every event, in the order they happened
+    fn on_event(&mut self, event: &GameEvent);
+}
+
+/// Holds the events emitted during a frame until they are handed out
+pub struct EventQueue {
+    events: VecDeque<GameEvent>,
+}
+
+impl EventQueue {
+    /// Creates an empty queue
+    pub fn new() -> EventQueue {
+        EventQueue {
+            events: VecDeque::new(),
+        }
+    }
+
+    /// Adds an event to the back of the queue
+    pub fn push(&mut self, event: GameEvent) {
+        self.events.push_back(event);
+    }
+
+    /// Adds several events to the back of the queue, keeping their order
+    pub fn extend<I: IntoIterator<Item = GameEvent>>(&mut self, events: I) {
+        self.events.extend(events);
+    }
+
+    /// Takes the oldest event off the queue
+    pub fn pop(&mut self) -> Option<GameEvent> {
+        self.events.pop_front()
+    }
+
+    /// Returns true if there are no events waiting
+    pub fn is_empty(&self) -> bool {
+        self.events.is_empty()
+    }
+}
+
+impl Default for EventQueue {
+    fn default() -> EventQueue {
+        EventQueue::new()
+    }
+}
+
+/// Unit tests for EventQueue.
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    #[test]
+    fn events_come_out_in_order() {
+        let mut queue = EventQueue::new();
+        queue.push(GameEvent::Hopped);
+        queue.extend(vec![GameEvent::ReachedNewRow, GameEvent::CubbyFilled(2)]);
+
+        assert_eq!(queue.pop(), Some(GameEvent::Hopped));
+        assert_eq!(queue.pop(), Some(GameEvent::ReachedNewRow));
+        assert_eq!(queue.pop(), Some(GameEvent::CubbyFilled(2)));
+        assert_eq!(queue.pop(), None);
+        assert!(queue.is_empty());
+    }
+}
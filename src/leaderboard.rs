@@ -0,0 +1,452 @@
+/*
+Copyright (c) 2018 Matt Carnovale, Julie Rutherford-Fields, Joshua Sander
+This work is available under the "MIT License”.
+Please see the file LICENSE in this distribution
+for license terms.
+*/
+
+use constants::{HIGH_SCORES_FILE, LEADERBOARD_SIZE, MAX_HEADERS, MAX_HEAD_BYTES, MAX_REQUEST_BYTES,
+                NET_CONNECT_TIMEOUT_MS, REQUEST_DEADLINE_MS};
+use ggez::{Context, GameError, GameResult};
+use replay::{self, Replay};
+use serde_json;
+use std::io::{self, BufRead, BufReader, ErrorKind, Read, Write};
+use std::net::{TcpStream, ToSocketAddrs};
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// One finished game on a high score table
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct ScoreEntry {
+    pub name: String,
+    pub score: isize,
+    /// The seed the board was built from
+    pub seed: u64,
+    /// The level the game ended on, starting from 1
+    pub level: u32,
+    /// Every hop made in the game, so the score can be checked
+    #[serde(default)]
+    pub replay: Replay,
+}
+
+/// The best scores, highest first. Used both for the local high
+/// score file and by the leaderboard server.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct Leaderboard {
+    entries: Vec<ScoreEntry>,
+}
+
+impl Leaderboard {
+    /// Creates an empty leaderboard
+    pub fn new() -> Leaderboard {
+        Leaderboard { entries: vec![] }
+    }
+
+    /// Returns the scores, highest first. Replays are left out
+    /// of what the server sends back, so they may be empty.
+    pub fn entries(&self) -> &[ScoreEntry] {
+        &self.entries
+    }
+
+    /// Adds a score if it is good enough to make the table.
+    /// Returns true if it did.
+    pub fn add(&mut self, entry: ScoreEntry) -> bool {
+        // A tie goes to whoever got there first
+        let position = self.entries
+            .iter()
+            .position(|existing| existing.score < entry.score)
+            .unwrap_or(self.entries.len());
+        if position >= LEADERBOARD_SIZE {
+            return false;
+        }
+        self.entries.insert(position, entry);
+        self.entries.truncate(LEADERBOARD_SIZE);
+        true
+    }
+
+    /// Plays the entry's replay back and adds it if the score checks
+    /// out. Returns None if it didn't, or whether it made the table.
+    pub fn submit(&mut self, entry: ScoreEntry) -> Option<bool> {
+        if replay::verify(&entry) {
+            Some(self.add(entry))
+        } else {
+            None
+        }
+    }
+
+    /// Parses a leaderboard written by write
+    pub fn read<R: Read>(reader: R) -> io::Result<Leaderboard> {
+        serde_json::from_reader(reader).map_err(|e| io::Error::new(ErrorKind::InvalidData, e))
+    }
+
+    /// Writes the leaderboard out as JSON
+    pub fn write<W: Write>(&self, writer: W) -> io::Result<()> {
+        serde_json::to_writer_pretty(writer, self)
+            .map_err(|e| io::Error::new(ErrorKind::InvalidData, e))
+    }
+
+    /// Reads the local high score file, starting a new
+    /// one if there is none yet or it can't be read
+    pub fn load(ctx: &mut Context) -> Leaderboard {
+        if !ctx.filesystem.exists(HIGH_SCORES_FILE) {
+            return Leaderboard::new();
+        }
+        match ctx.filesystem.open(HIGH_SCORES_FILE) {
+            Ok(file) => Leaderboard::read(file).unwrap_or_else(|e| {
+                println!("Could not read high scores: {}", e);
+                Leaderboard::new()
+            }),
+            Err(e) => {
+                println!("Could not open high scores: {}", e);
+                Leaderboard::new()
+            }
+        }
+    }
+
+    /// Writes the local high score file
+    pub fn save(&self, ctx: &mut Context) -> GameResult<()> {
+        let file = ctx.filesystem.create(HIGH_SCORES_FILE)?;
+        self.write(file).map_err(|e| GameError::UnknownError(e.to_string()))
+    }
+}
+
+/// The parts of an HTTP request or response the leaderboard uses
+#[derive(Clone, Debug, PartialEq)]
+pub struct HttpMessage {
+    /// The request line or status line, e.g. "GET /scores HTTP/1.1"
+    pub start: String,
+    pub body: Vec<u8>,
+}
+
+/// Reads one HTTP message, using Content-Length to find the end of the body.
+/// Bodies longer than MAX_REQUEST_BYTES are refused rather than read.
+pub fn read_message<R: BufRead>(reader: &mut R) -> io::Result<HttpMessage> {
+    let (start, length) = read_head(reader)?;
+    if length > MAX_REQUEST_BYTES {
+        return Err(io::Error::new(ErrorKind::InvalidData, "message body too large"));
+    }
+    read_body(reader, start, length)
+}
+
+/// Reads the start line and headers of an HTTP message.
+/// Returns the start line and the length of the body that follows.
+/// Heads longer than MAX_HEAD_BYTES or MAX_HEADERS are refused.
+fn read_head<R: BufRead>(reader: &mut R) -> io::Result<(String, usize)> {
+    let mut head = reader.by_ref().take(MAX_HEAD_BYTES);
+    let start = read_head_line(&mut head)?;
+    if start.is_empty() {
+        return Err(io::Error::new(ErrorKind::UnexpectedEof, "empty message"));
+    }
+
+    let mut length = 0;
+    let mut headers = 0;
+    loop {
+        let header = read_head_line(&mut head)?;
+        if header.trim().is_empty() {
+            break;
+        }
+        headers += 1;
+        if headers > MAX_HEADERS {
+            return Err(io::Error::new(ErrorKind::InvalidData, "too many headers"));
+        }
+        let mut parts = header.splitn(2, ':');
+        let name = parts.next().unwrap_or("").trim();
+        if name.eq_ignore_ascii_case("content-length") {
+            length = parts
+                .next()
+                .unwrap_or("")
+                .trim()
+                .parse()
+                .map_err(|e| io::Error::new(ErrorKind::InvalidData, e))?;
+        }
+    }
+    Ok((start.trim().to_string(), length))
+}
+
+/// Reads one line of a message's head. A line cut off by the
+/// end of the allowed head means the head was too long.
+fn read_head_line<R: BufRead>(head: &mut io::Take<R>) -> io::Result<String> {
+    let mut line = String::new();
+    head.read_line(&mut line)?;
+    if !line.ends_with('\n') && head.limit() == 0 {
+        return Err(io::Error::new(ErrorKind::InvalidData, "message head too large"));
+    }
+    Ok(line)
+}
+
+/// Reads the body following a message's headers
+fn read_body<R: BufRead>(reader: &mut R, start: String, length: usize) -> io::Result<HttpMessage> {
+    let mut body = vec![0; length];
+    reader.read_exact(&mut body)?;
+    Ok(HttpMessage {
+        start: start,
+        body: body,
+    })
+}
+
+/// Writes one HTTP message with a JSON body and closes the connection afterwards
+pub fn write_message<W: Write>(writer: &mut W, start: &str, body: &[u8]) -> io::Result<()> {
+    write!(
+        writer,
+        "{}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+        start,
+        body.len()
+    )?;
+    writer.write_all(body)?;
+    writer.flush()
+}
+
+/// Works out the server's answer to a request. Scores are listed
+/// with "GET /scores" and submitted with "POST /scores". Returns the
+/// status line, the body and whether the leaderboard changed.
+pub fn respond(leaderboard: &mut Leaderboard, request: &HttpMessage) -> (String, Vec<u8>, bool) {
+    let mut words = request.start.split_whitespace();
+    let method = words.next().unwrap_or("");
+    let path = words.next().unwrap_or("");
+    match (method, path) {
+        ("GET", "/scores") => {
+            // Replays can be long and nobody looking at the table needs them
+            let entries: Vec<ScoreEntry> = leaderboard
+                .entries()
+                .iter()
+                .map(|entry| ScoreEntry {
+                    replay: Replay::new(),
+                    ..entry.clone()
+                })
+                .collect();
+            let body = serde_json::to_vec(&entries).unwrap_or_default();
+            ("HTTP/1.1 200 OK".to_string(), body, false)
+        }
+        ("POST", "/scores") => match serde_json::from_slice::<ScoreEntry>(&request.body) {
+            Ok(entry) => match leaderboard.submit(entry) {
+                Some(added) => {
+                    let body = serde_json::to_vec(&added).unwrap_or_default();
+                    ("HTTP/1.1 200 OK".to_string(), body, added)
+                }
+                None => {
+                    let body = b"\"score does not match its replay\"".to_vec();
+                    ("HTTP/1.1 422 Unprocessable Entity".to_string(), body, false)
+                }
+            },
+            Err(e) => {
+                let body = serde_json::to_vec(&e.to_string()).unwrap_or_default();
+                ("HTTP/1.1 400 Bad Request".to_string(), body, false)
+            }
+        },
+        _ => ("HTTP/1.1 404 Not Found".to_string(), b"\"not found\"".to_vec(), false),
+    }
+}
+
+/// A connection that stops reading once a deadline has passed,
+/// however slowly the bytes before it trickled in
+struct DeadlineStream {
+    stream: TcpStream,
+    deadline: Instant,
+}
+
+impl Read for DeadlineStream {
+    fn read(&mut self, buffer: &mut [u8]) -> io::Result<usize> {
+        let now = Instant::now();
+        if now >= self.deadline {
+            return Err(io::Error::new(ErrorKind::TimedOut, "request took too long"));
+        }
+        self.stream.set_read_timeout(Some(self.deadline - now))?;
+        self.stream.read(buffer)
+    }
+}
+
+/// Answers one request to the leaderboard server.
+/// Returns true if the leaderboard changed.
+pub fn serve(leaderboard: &mut Leaderboard, stream: TcpStream) -> io::Result<bool> {
+    // Don't let a client that never finishes its request hold up everyone else
+    stream.set_write_timeout(Some(Duration::from_millis(NET_CONNECT_TIMEOUT_MS)))?;
+    let mut reader = BufReader::new(DeadlineStream {
+        stream: stream,
+        deadline: Instant::now() + Duration::from_millis(REQUEST_DEADLINE_MS),
+    });
+    let (start, length) = read_head(&mut reader)?;
+    // Turn away anything too big to be a score before making room for it
+    let (status, body, changed) = if length > MAX_REQUEST_BYTES {
+        let body = b"\"request too large\"".to_vec();
+        ("HTTP/1.1 413 Payload Too Large".to_string(), body, false)
+    } else {
+        let request = read_body(&mut reader, start, length)?;
+        respond(leaderboard, &request)
+    };
+    write_message(&mut reader.get_mut().stream, &status, &body)?;
+    Ok(changed)
+}
+
+/// Sends a request to the leaderboard server and returns the body of a successful response
+fn request(address: &str, start: &str, body: &[u8]) -> io::Result<Vec<u8>> {
+    let socket_address = address
+        .to_socket_addrs()?
+        .next()
+        .ok_or_else(|| io::Error::new(ErrorKind::InvalidInput, "no address to connect to"))?;
+    let timeout = Duration::from_millis(NET_CONNECT_TIMEOUT_MS);
+    let mut stream = TcpStream::connect_timeout(&socket_address, timeout)?;
+    stream.set_read_timeout(Some(timeout))?;
+    stream.set_write_timeout(Some(timeout))?;
+
+    let start = format!("{}\r\nHost: {}", start, address);
+    write_message(&mut stream, &start, body)?;
+    let response = read_message(&mut BufReader::new(stream))?;
+    if response.start.split_whitespace().nth(1) != Some("200") {
+        return Err(io::Error::new(ErrorKind::Other, response.start));
+    }
+    Ok(response.body)
+}
+
+/// Sends a finished game's score to the leaderboard server.
+/// Returns true if it made the table.
+pub fn submit_score(address: &str, entry: &ScoreEntry) -> io::Result<bool> {
+    let body = serde_json::to_vec(entry).map_err(|e| io::Error::new(ErrorKind::InvalidData, e))?;
+    let response = request(address, "POST /scores HTTP/1.1", &body)?;
+    serde_json::from_slice(&response).map_err(|e| io::Error::new(ErrorKind::InvalidData, e))
+}
+
+/// Fetches the top scores from the leaderboard server, highest first
+pub fn fetch_scores(address: &str) -> io::Result<Vec<ScoreEntry>> {
+    let response = request(address, "GET /scores HTTP/1.1", &[])?;
+    serde_json::from_slice(&response).map_err(|e| io::Error::new(ErrorKind::InvalidData, e))
+}
+
+/// Plays a finished game's replay back and, if the score holds up, sends
+/// it to the leaderboard server, all on its own thread so the game carries
+/// on meanwhile. Scores that check out are also handed back on checked to
+/// go in the local high score file. Failures are reported.
+pub fn record_score_in_background(address: String, entry: ScoreEntry, checked: Sender<ScoreEntry>) {
+    thread::spawn(move || {
+        if !replay::verify(&entry) {
+            println!("Score does not match its replay, not recording it");
+            return;
+        }
+        // Nobody is left to tell if the game has closed
+        let _ = checked.send(entry.clone());
+        if let Err(e) = submit_score(&address, &entry) {
+            println!("Could not send score to the leaderboard: {}", e);
+        }
+    });
+}
+
+/// Fetches the top scores on their own thread so the game carries on
+/// while it waits. The scores, or why they couldn't be fetched, arrive
+/// on the returned channel.
+pub fn fetch_scores_in_background(address: String) -> Receiver<io::Result<Vec<ScoreEntry>>> {
+    let (sender, receiver) = mpsc::channel();
+    thread::spawn(move || {
+        // Nobody is left to tell if the game has stopped waiting
+        let _ = sender.send(fetch_scores(&address));
+    });
+    receiver
+}
+
+/// Unit tests for Leaderboard and the leaderboard server.
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use std::net::TcpListener;
+    use std::thread;
+
+    fn entry(name: &str, score: isize) -> ScoreEntry {
+        ScoreEntry {
+            name: name.to_string(),
+            score: score,
+            seed: 0,
+            level: 1,
+            replay: Replay::new(),
+        }
+    }
+
+    #[test]
+    fn scores_are_kept_highest_first() {
+        let mut leaderboard = Leaderboard::new();
+
+        leaderboard.add(entry("B", 100));
+        leaderboard.add(entry("A", 300));
+        leaderboard.add(entry("C", 100));
+
+        let names: Vec<&str> = leaderboard
+            .entries()
+            .iter()
+            .map(|entry| entry.name.as_str())
+            .collect();
+        assert_eq!(names, vec!["A", "B", "C"]);
+    }
+
+    #[test]
+    fn low_scores_drop_off_a_full_table() {
+        let mut leaderboard = Leaderboard::new();
+        for score in 0..LEADERBOARD_SIZE {
+            leaderboard.add(entry("A", 10 + score as isize));
+        }
+
+        assert!(!leaderboard.add(entry("B", 10)));
+        assert!(leaderboard.add(entry("C", 1000)));
+        assert_eq!(leaderboard.entries().len(), LEADERBOARD_SIZE);
+        assert_eq!(leaderboard.entries()[0].name, "C");
+    }
+
+    #[test]
+    fn made_up_scores_are_turned_away_by_the_server() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap().to_string();
+        let server = thread::spawn(move || {
+            let mut leaderboard = Leaderboard::new();
+            for stream in listener.incoming().take(2) {
+                serve(&mut leaderboard, stream.unwrap()).unwrap();
+            }
+        });
+
+        // There is no replay to back this score up
+        assert!(submit_score(&address, &entry("A", 500)).is_err());
+        assert_eq!(fetch_scores(&address).unwrap(), vec![]);
+        server.join().unwrap();
+    }
+
+    #[test]
+    fn oversized_requests_are_refused() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap();
+        let server = thread::spawn(move || {
+            let mut leaderboard = Leaderboard::new();
+            let stream = listener.incoming().next().unwrap().unwrap();
+            serve(&mut leaderboard, stream).unwrap()
+        });
+
+        let mut stream = TcpStream::connect(address).unwrap();
+        let huge = format!("Content-Length: {}", MAX_REQUEST_BYTES + 1);
+        write!(stream, "POST /scores HTTP/1.1\r\n{}\r\n\r\n", huge).unwrap();
+        let response = read_message(&mut BufReader::new(stream)).unwrap();
+
+        assert_eq!(response.start, "HTTP/1.1 413 Payload Too Large");
+        assert!(!server.join().unwrap());
+    }
+
+    #[test]
+    fn heads_that_never_end_are_refused() {
+        let long_line = format!("GET /{} HTTP/1.1\r\n\r\n", "a".repeat(MAX_HEAD_BYTES as usize));
+        let many_headers = format!("GET /scores HTTP/1.1\r\n{}\r\n", "X: y\r\n".repeat(MAX_HEADERS + 1));
+
+        for request in &[long_line, many_headers] {
+            let error = read_message(&mut io::Cursor::new(request.as_bytes())).unwrap_err();
+            assert_eq!(error.kind(), ErrorKind::InvalidData);
+        }
+    }
+
+    #[test]
+    fn unknown_paths_are_not_found() {
+        let mut leaderboard = Leaderboard::new();
+        let request = HttpMessage {
+            start: "GET /nowhere HTTP/1.1".to_string(),
+            body: vec![],
+        };
+
+        let (status, _, changed) = respond(&mut leaderboard, &request);
+
+        assert_eq!(status, "HTTP/1.1 404 Not Found");
+        assert!(!changed);
+    }
+}
@@ -0,0 +1,39 @@
+/*
+Copyright (c) 2018 Matt Carnovale, Julie Rutherford-Fields, Joshua Sander
+This work is available under the "MIT License”.
+Please see the file LICENSE in this distribution
+for license terms.
+*/
+
+extern crate ggez;
+extern crate rand;
+extern crate serde;
+#[macro_use]
+extern crate serde_derive;
+extern crate serde_json;
+
+pub mod assets;
+pub mod audio;
+pub mod background;
+pub mod characters;
+pub mod collisions;
+pub mod constants;
+pub mod controls;
+pub mod debug;
+pub mod events;
+pub mod leaderboard;
+pub mod menu;
+pub mod net;
+pub mod particles;
+pub mod replay;
+pub mod rewind;
+pub mod river;
+pub mod rng;
+pub mod save;
+pub mod settings;
+pub mod spectate;
+pub mod sprites;
+pub mod stats;
+pub mod theme;
+pub mod traffic;
+pub mod turns;
@@ -5,28 +5,86 @@ Please see the file LICENSE in this distribution
 for license terms.
 */
 
+extern crate crabber;
 extern crate ggez;
-extern crate rand;
 
-pub mod background;
-pub mod characters;
-pub mod constants;
-pub mod river;
-pub mod sprites;
-pub mod traffic;
+use crabber::assets::{Assets, FontSize};
+use crabber::audio::{MusicTrack, Sounds};
+use crabber::background::{Cubbies, HighScores, Lobby, Menu, OptionsMenu, PauseMenu, River,
+                          Road};
+use crabber::characters::{Crab, DeathCause};
+use crabber::controls::{button_action, Action, GamepadInput, KeyMap};
+use crabber::debug::DebugOverlay;
+use crabber::events::{EventQueue, GameEvent, GameEventListener};
+use crabber::leaderboard::{self, Leaderboard, ScoreEntry};
+use crabber::menu::{MenuChoice, MenuCommand, MenuList};
+use crabber::net::NetSession;
+use crabber::particles::{self, ParticleSystem};
+use crabber::replay::Replay;
+use crabber::rewind::{RewindBuffer, Snapshot};
+use crabber::rng::{random_seed, GameRng};
+use crabber::save::SaveGame;
+use crabber::settings::{step_volume, Settings};
+use crabber::sprites::DrawBatch;
+use crabber::spectate::{Broadcaster, Spectator, SpectatorFrame};
+use crabber::stats::GameStats;
+use crabber::theme::{step_theme, Theme};
+use crabber::turns::Turns;
+use crabber::{collisions, menu, net, river, traffic};
 
-use background::{Cubbies, Menu, River, Road};
-use characters::Crab;
+use crabber::constants::{ANNOUNCEMENT_FRAMES, COOP_START_OFFSET, CUBBY_HOLD_FRAMES,
+                         DEATH_ANIMATION_FRAMES, LANE_MODIFIER, NET_PORT, NET_STALL_FRAMES, NUM_LANE, NUM_LOG,
+                         REWIND_FRAMES, RIVER_LANE_MODIFIER, SPECTATE_PORT,
+                         SQUARE_SIZE, START, TRUCK_W, TURN_BANNER_FRAMES, VERSUS_CUBBIES,
+                         WINNING_CUBBIES, WIN_H, WIN_W};
 
-use constants::{COLLISIONS_ON, END, GRASS, LANE_MODIFIER, LOG_EDGE_BUFFER, MID_ROW, NUM_LANE,
-                NUM_LOG, RIVER_LANE_MODIFIER, SQUARE_SIZE, START, WINNING_CUBBIES, WIN_H, WIN_W};
-
-use ggez::event::{Keycode, Mod};
+use ggez::event::{Axis, Button, Keycode, Mod};
 use ggez::graphics;
 use ggez::graphics::set_background_color;
-use ggez::{Context, GameResult};
-use ggez::{conf, event, timer};
-use std::time::Duration;
+use ggez::{Context, GameError, GameResult};
+use ggez::{conf, event};
+use std::io;
+use std::net::TcpListener;
+use std::process;
+use std::sync::mpsc::{self, Receiver, Sender, TryRecvError};
+use std::time::Instant;
+
+/// The kind of game being played
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum GameMode {
+    /// One player going for a high score
+    Arcade,
+    /// One player with rewind and no score
+    Practice,
+    /// Two players taking turns, each with their own crab and cubbies
+    TwoPlayer,
+    /// Two crabs on the board at once, filling the same cubbies
+    CoOp,
+    /// Two crabs racing to claim the most cubbies, with no lives to lose
+    Versus,
+    /// Co-op with the second crab played from another computer
+    Online,
+}
+
+/// The screen currently being shown
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Screen {
+    MainMenu,
+    Playing,
+    Paused,
+    Options,
+    Lobby,
+    Spectating,
+    Scores,
+}
+
+/// A message shown over the board while it is held still
+struct Announcement {
+    text: String,
+    frames: u32,
+    /// Go back to the main menu once the message has been shown
+    then_menu: bool,
+}
 
 struct MainState {
     road: Road,
@@ -37,18 +95,66 @@ struct MainState {
     lane_modifier: f32,
     river_lanes: Vec<river::RiverLane>,
     river_lane_modifier: f32,
-    game_over_man: graphics::Text,
-    main_menu: bool,
-    selection: u32,
+    screen: Screen,
+    main_menu: MenuList<MenuChoice>,
+    pause_menu: MenuList<MenuChoice>,
+    options_menu: MenuList<MenuChoice>,
+    options_return: Screen,
+    rng: GameRng,
+    seed: u64,
+    level: u32,
+    replay: Replay,
+    mode: GameMode,
+    turns: Option<Turns>,
+    turn_banner: u32,
+    death_frames: u32,
+    hold_frames: u32,
+    announcement: Option<Announcement>,
+    partner: Option<Crab>,
+    partner_keys: KeyMap,
+    listener: Option<TcpListener>,
+    net: Option<NetSession>,
+    pending_actions: Vec<Action>,
+    net_stall: u32,
+    broadcaster: Option<Broadcaster>,
+    spectator: Option<Spectator>,
+    high_scores: Vec<ScoreEntry>,
+    scores_title: &'static str,
+    scores_request: Option<Receiver<io::Result<Vec<ScoreEntry>>>>,
+    score_checker: Sender<ScoreEntry>,
+    checked_scores: Receiver<ScoreEntry>,
+    theme: Theme,
+    theme_names: Vec<String>,
+    assets: Assets,
+    background_layer: DrawBatch,
+    traffic_layer: DrawBatch,
+    river_layer: DrawBatch,
+    particles: ParticleSystem,
+    debug: DebugOverlay,
+    invincible_used: bool,
+    rewinding: bool,
+    rewind: RewindBuffer,
+    events: EventQueue,
+    stats: GameStats,
+    settings: Settings,
+    sounds: Sounds,
+    key_map: KeyMap,
+    rebinding: Option<Action>,
+    gamepad: GamepadInput,
 }
 
 impl MainState {
     fn new(_ctx: &mut Context) -> GameResult<MainState> {
-        let font = graphics::Font::new(_ctx, "/game_over.ttf", 48).unwrap();
-        let text = graphics::Text::new(_ctx, "Game Over Man!", &font)?;
         let lanes = vec![];
         let river_lanes = vec![];
-        let s = MainState {
+        let settings = Settings::load(_ctx);
+        let sounds = Sounds::load(_ctx, settings.music_volume, settings.effects_volume);
+        let key_map = KeyMap::from_names(&settings.key_bindings);
+        let theme = settings.color_mode.apply(Theme::load(_ctx, &settings.theme));
+        let assets = Assets::load(_ctx, &theme)?;
+        set_background_color(_ctx, theme.grass());
+        let (score_checker, checked_scores) = mpsc::channel();
+        let mut s = MainState {
             road: Road::new(WIN_W, WIN_H),
             river: River::new(WIN_W, WIN_H),
             cubbies: Cubbies::construct(),
@@ -57,138 +163,1118 @@ impl MainState {
             lane_modifier: LANE_MODIFIER,
             river_lanes: river_lanes,
             river_lane_modifier: RIVER_LANE_MODIFIER,
-            game_over_man: text,
-            main_menu: true,
-            selection: 0,
+            screen: Screen::MainMenu,
+            main_menu: menu::main_menu(),
+            pause_menu: menu::pause_menu(),
+            options_menu: menu::options_menu(),
+            options_return: Screen::MainMenu,
+            rng: GameRng::from_entropy(),
+            seed: 0,
+            level: 1,
+            replay: Replay::new(),
+            mode: GameMode::Arcade,
+            turns: None,
+            turn_banner: 0,
+            death_frames: 0,
+            hold_frames: 0,
+            announcement: None,
+            partner: None,
+            partner_keys: KeyMap::player_two(),
+            listener: None,
+            net: None,
+            pending_actions: vec![],
+            net_stall: 0,
+            broadcaster: None,
+            spectator: None,
+            high_scores: vec![],
+            scores_title: "",
+            scores_request: None,
+            score_checker: score_checker,
+            checked_scores: checked_scores,
+            background_layer: DrawBatch::new(None),
+            traffic_layer: DrawBatch::new(assets.sprites.as_ref()),
+            river_layer: DrawBatch::new(assets.sprites.as_ref()),
+            particles: ParticleSystem::new(),
+            debug: DebugOverlay::new(),
+            invincible_used: false,
+            assets: assets,
+            theme_names: Theme::available(_ctx),
+            theme: theme,
+            rewinding: false,
+            rewind: RewindBuffer::new(REWIND_FRAMES),
+            events: EventQueue::new(),
+            stats: GameStats::default(),
+            settings: settings,
+            sounds: sounds,
+            key_map: key_map,
+            rebinding: None,
+            gamepad: GamepadInput::new(),
         };
+        s.apply_broadcast();
         Ok(s)
     }
-}
 
-impl event::EventHandler for MainState {
-    fn update(&mut self, _ctx: &mut Context) -> GameResult<()> {
-        // Create new lanes
-        if (self.lanes.len() as u32) < NUM_LANE {
-            self.lanes
-                .push(traffic::Lane::construct(self.lane_modifier));
-            self.lane_modifier += 1.0;
+    /// Hands every event emitted this frame to the systems listening for them
+    fn dispatch_events(&mut self) {
+        self.events.extend(self.player.take_events());
+        if let Some(ref mut partner) = self.partner {
+            self.events.extend(partner.take_events());
+        }
+        while let Some(event) = self.events.pop() {
+            self.stats.on_event(&event);
+            self.sounds.on_event(&event);
         }
+    }
 
-        if COLLISIONS_ON {
-            // Check for collisions
-            // with water
-            if self.player.get_bottom_edge() <= MID_ROW as f32 * SQUARE_SIZE - SQUARE_SIZE
-                && self.player.get_bottom_edge() >= END
-            {
-                let mut collided = true;
-
-                'outerLog: for i in 0..self.river_lanes.len() {
-                    for j in 0..self.river_lanes[i].river_transports.len() {
-                        // Assume the crab is safe
-                        let mut inside = true;
-
-                        if self.player.get_right_edge()
-                            > self.river_lanes[i].river_transports[j].get_right_edge()
-                                + LOG_EDGE_BUFFER
-                        {
-                            inside = false;
-                        }
-
-                        if self.player.get_left_edge()
-                            < self.river_lanes[i].river_transports[j].get_left_edge()
-                                - LOG_EDGE_BUFFER
-                        {
-                            inside = false;
-                        }
-
-                        if self.player.get_bottom_edge()
-                            < self.river_lanes[i].river_transports[j].get_bottom_edge()
-                        {
-                            inside = false;
-                        }
-
-                        if self.player.get_top_edge()
-                            > self.river_lanes[i].river_transports[j].get_top_edge()
-                        {
-                            inside = false;
-                        }
-
-                        //The crab has passed all of the checks for a particular log, meaning it is inside
-                        if inside == true {
-                            self.player.set_direction(
-                                self.river_lanes[i].river_transports[j].get_direction(),
-                            );
-                            self.player
-                                .set_speed(self.river_lanes[i].river_transports[j].get_speed());
-                            collided = false;
-                            break 'outerLog;
-                        }
-                    }
+    /// Writes the settings to disk, reporting rather than failing if it can't
+    fn save_settings(&mut self, ctx: &mut Context) {
+        if let Err(e) = self.settings.save(ctx) {
+            println!("Could not save settings: {}", e);
+        }
+    }
+
+    /// Draws the background, cubbies and every lane, but not the crab.
+    /// Each layer is batched so it takes a few draw calls however busy it is.
+    fn draw_board(&mut self, ctx: &mut Context) -> GameResult<()> {
+        //Draw background
+        self.road.draw(&mut self.background_layer, &self.theme);
+        self.river.draw(&mut self.background_layer, &self.theme);
+        self.cubbies.draw(&mut self.background_layer, &self.theme);
+        self.background_layer.draw(ctx)?;
+        self.cubbies.draw_crabs(ctx, &self.assets, &self.theme)?;
+
+        //Draw our lanes
+        for lane in &self.lanes {
+            lane.draw_vehicles_in_lane(&mut self.traffic_layer, &self.theme);
+        }
+        self.traffic_layer.draw(ctx)?;
+
+        //Draw our river lanes
+        for river_lane in &self.river_lanes {
+            river_lane.draw_river_transports_in_river_lane(&mut self.river_layer, &self.theme);
+        }
+        self.river_layer.draw(ctx)?;
+
+        Ok(())
+    }
+
+    /// Draws the lives and score along the bottom of the screen
+    fn draw_hud(&mut self, ctx: &mut Context) -> GameResult<()> {
+        //Draw the lives in the bottom left, saying whose they are when taking turns
+        let lives = if self.mode == GameMode::Versus {
+            // Races are won on cubbies rather than lives
+            format! {
+                "Cubbies: P1 {}  P2 {}  of {}",
+                self.cubbies.get_claimed(1),
+                self.cubbies.get_claimed(2),
+                VERSUS_CUBBIES
+            }
+        } else if let Some(ref turns) = self.turns {
+            format! {"P{} Lives: {}", turns.current(), self.player.get_lives()}
+        } else if let Some(ref mut partner) = self.partner {
+            format! {"Lives: P1 {}  P2 {}", self.player.get_lives(), partner.get_lives()}
+        } else {
+            format! {"Lives: {}", self.player.get_lives()}
+        };
+        let lives_text = self.assets.lives_text(ctx, &lives)?;
+        let dest_point = graphics::Point2::new(0 as f32, WIN_H as f32 - SQUARE_SIZE);
+        graphics::draw(ctx, lives_text, dest_point, 0.0)?;
+
+        //Draw the score in the bottom right, practice runs don't score
+        let score = if self.mode == GameMode::Practice {
+            format! {"Practice - hold R to rewind"}
+        } else if let Some(ref turns) = self.turns {
+            // Both scores are shown, in player order
+            let current = self.player.get_score();
+            let waiting = turns.waiting().crab.score;
+            let (one, two) = if turns.current() == 1 {
+                (current, waiting)
+            } else {
+                (waiting, current)
+            };
+            format! {"1UP: {}  2UP: {}", one, two}
+        } else if let Some(ref mut partner) = self.partner {
+            // Co-op crabs keep their own scores
+            format! {"1UP: {}  2UP: {}", self.player.get_score(), partner.get_score()}
+        } else {
+            format! {"Score: {}", self.player.get_score()}
+        };
+        let score_text = self.assets.score_text(ctx, &score)?;
+        let score_width = score_text.width() as f32;
+        let dest_point =
+            graphics::Point2::new(WIN_W as f32 - score_width, WIN_H as f32 - SQUARE_SIZE);
+        graphics::draw(ctx, score_text, dest_point, 0.0)?;
+
+        Ok(())
+    }
+
+    /// Draws every crab still in the game, and any that has just
+    /// died partway through its death animation where it died
+    fn draw_crabs(&mut self, ctx: &mut Context) -> GameResult<()> {
+        let progress = 1.0 - self.death_frames as f32 / DEATH_ANIMATION_FRAMES as f32;
+        let mut crabs = vec![&mut self.player];
+        if let Some(ref mut partner) = self.partner {
+            crabs.push(partner);
+        }
+        for crab in crabs {
+            if crab.get_life_lost() && self.death_frames > 0 {
+                crab.draw_death(ctx, &self.assets, progress)?;
+            } else if crab.get_lives() > 0 {
+                crab.draw(ctx, &self.assets)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Draws what killed the crab while its death animation plays,
+    /// the player's if both crabs died at once
+    fn draw_death_message(&mut self, ctx: &mut Context) -> GameResult<()> {
+        let mut last_death = None;
+        if self.player.get_life_lost() {
+            last_death = self.player.get_last_death();
+        } else if let Some(ref mut partner) = self.partner {
+            if partner.get_life_lost() {
+                last_death = partner.get_last_death();
+            }
+        }
+        match last_death {
+            Some(cause) => {
+                self.assets
+                    .draw_centered(ctx, FontSize::Large, cause.message(), WIN_H as f32 / 2.0)
+            }
+            None => Ok(()),
+        }
+    }
+
+    /// Returns true if the board is held still and nobody can move
+    fn board_held(&self) -> bool {
+        self.death_frames > 0 || self.hold_frames > 0 || self.announcement.is_some()
+    }
+
+    /// Holds the board still while a message is shown over it,
+    /// going back to the main menu afterwards if then_menu is set
+    fn announce(&mut self, text: String, then_menu: bool) {
+        self.announcement = Some(Announcement {
+            text: text,
+            frames: ANNOUNCEMENT_FRAMES,
+            then_menu: then_menu,
+        });
+    }
+
+    /// Returns true if nobody has any lives left to play.
+    /// With two players the game only ends once both are out of lives.
+    fn out_of_lives(&mut self) -> bool {
+        self.player.get_lives() <= 0
+            && !self.turns.as_ref().map_or(false, |turns| turns.waiting_can_play())
+            && !self.partner.as_mut().map_or(false, |partner| partner.get_lives() > 0)
+    }
+
+    /// Ends a game nobody has lives left in: records the score,
+    /// sets the board up for the next game and says it is over
+    fn game_over(&mut self, ctx: &mut Context) {
+        self.cubbies = Cubbies::construct();
+        self.events.push(GameEvent::GameOver);
+        if self.mode == GameMode::Arcade {
+            self.record_score();
+            // Each score is checked by playing its game back on its own,
+            // so the next game starts from a fresh board and replay
+            self.reset_board(random_seed());
+        }
+        self.player.set_lives();
+        self.player.reset_score();
+        if let Some(ref mut turns) = self.turns {
+            turns.reset_waiting();
+        }
+        if let Some(ref mut partner) = self.partner {
+            partner.set_lives();
+            partner.reset_score();
+        }
+        // A lost game can't be continued
+        if self.mode != GameMode::Practice {
+            if let Err(e) = SaveGame::delete(ctx) {
+                println!("Could not remove save game: {}", e);
+            }
+        }
+        self.announce("Game Over Man!".to_string(), false);
+    }
+
+    /// Puts every crab that died back at the start once its death animation
+    /// has played, and hands the board over when players take turns
+    fn respawn_crabs(&mut self) {
+        //Take a life
+        if self.player.get_life_lost() == true {
+            self.player.respawn();
+
+            // Racing crabs never run out of lives
+            if self.mode == GameMode::Versus {
+                self.player.set_lives();
+            }
+
+            // Hand the board to the other player if they have lives left
+            if let Some(ref mut turns) = self.turns {
+                if turns.pass(&mut self.player, &mut self.cubbies) {
+                    self.turn_banner = TURN_BANNER_FRAMES;
                 }
+            }
+        }
 
-                if collided {
-                    self.player.lose_life();
+        //Take a life from the second crab
+        if let Some(ref mut partner) = self.partner {
+            if partner.get_life_lost() {
+                partner.respawn();
+                if self.mode == GameMode::Versus {
+                    partner.set_lives();
                 }
+            }
+        }
+    }
+
+    /// Throws up a splash where any crab that just drowned went under
+    fn splash_drowned_crabs(&mut self) {
+        let mut crabs = vec![&mut self.player];
+        if let Some(ref mut partner) = self.partner {
+            crabs.push(partner);
+        }
+        for crab in crabs {
+            let drowned = match crab.get_last_death() {
+                Some(DeathCause::Drowned) | Some(DeathCause::CarriedOffscreen) => true,
+                _ => false,
+            };
+            if crab.get_life_lost() && drowned {
+                let state = crab.get_state();
+                self.particles.add(particles::splash(
+                    state.x + SQUARE_SIZE / 2.0,
+                    state.y + SQUARE_SIZE / 2.0,
+                ));
+            }
+        }
+    }
+
+    /// Leaves foam behind every log and turtle and puffs smoke out of every truck
+    fn emit_trails(&mut self) {
+        for lane in &self.lanes {
+            for vehicle in &lane.vehicles {
+                let (left, right) = (vehicle.get_left_edge(), vehicle.get_right_edge());
+                if right - left >= TRUCK_W {
+                    let direction = vehicle.get_direction();
+                    let back = if direction { left } else { right };
+                    let y = vehicle.get_bottom_edge() + SQUARE_SIZE * 0.7;
+                    self.particles.exhaust(back, y, direction);
+                }
+            }
+        }
+        for river_lane in &self.river_lanes {
+            for transport in &river_lane.river_transports {
+                let direction = transport.get_direction();
+                let back = if direction {
+                    transport.get_left_edge()
+                } else {
+                    transport.get_right_edge()
+                };
+                let y = transport.get_bottom_edge() + SQUARE_SIZE / 2.0;
+                self.particles.wake(back, y, direction);
+            }
+        }
+    }
+
+    /// Records the crab, the cubbies and the position of every lane object for this frame
+    fn take_snapshot(&self) -> Snapshot {
+        Snapshot {
+            crab: self.player.get_state(),
+            cubbies: self.cubbies.get_state(),
+            lanes: self.lanes.iter().map(|lane| lane.get_positions()).collect(),
+            river_lanes: self.river_lanes
+                .iter()
+                .map(|river_lane| river_lane.get_positions())
+                .collect(),
+        }
+    }
+
+    /// Puts the crab, the cubbies and every lane object back how they were in a snapshot
+    fn restore_snapshot(&mut self, snapshot: &Snapshot) {
+        self.player.set_state(&snapshot.crab);
+        self.cubbies = Cubbies::from_state(&snapshot.cubbies);
+        for (lane, positions) in self.lanes.iter_mut().zip(&snapshot.lanes) {
+            lane.set_positions(positions);
+        }
+        for (river_lane, positions) in self.river_lanes.iter_mut().zip(&snapshot.river_lanes) {
+            river_lane.set_positions(positions);
+        }
+    }
+
+    /// Clears the board so it is built up again from the given seed,
+    /// back on the first level with nothing recorded in the replay
+    fn reset_board(&mut self, seed: u64) {
+        self.cubbies = Cubbies::construct();
+        self.lanes = vec![];
+        self.lane_modifier = LANE_MODIFIER;
+        self.river_lanes = vec![];
+        self.river_lane_modifier = RIVER_LANE_MODIFIER;
+        self.seed = seed;
+        self.rng = GameRng::new(seed);
+        self.level = 1;
+        self.replay = Replay::new();
+    }
+
+    /// Throws away the current board and starts over with a fresh one
+    fn new_game(&mut self, mode: GameMode) {
+        self.player = Crab::new(WIN_W, START as u32);
+        self.reset_board(random_seed());
+        self.death_frames = 0;
+        self.hold_frames = 0;
+        self.announcement = None;
+        self.particles.clear();
+        self.invincible_used = self.debug.is_invincible();
+        self.mode = mode;
+        if mode == GameMode::TwoPlayer {
+            self.turns = Some(Turns::new());
+            self.turn_banner = TURN_BANNER_FRAMES;
+        } else {
+            self.turns = None;
+            self.turn_banner = 0;
+        }
+        match mode {
+            GameMode::CoOp | GameMode::Versus | GameMode::Online => self.add_partner(),
+            GameMode::Arcade | GameMode::Practice | GameMode::TwoPlayer => self.partner = None,
+        }
+        self.net = None;
+        self.pending_actions.clear();
+        self.net_stall = 0;
+        self.rewinding = false;
+        self.rewind.clear();
+        self.screen = Screen::Playing;
+    }
+
+    /// Puts a second crab on the board for co-op, the two
+    /// crabs starting either side of the middle column
+    fn add_partner(&mut self) {
+        let center = WIN_W as f32 / 2.0;
+        self.player.set_start_x(center - COOP_START_OFFSET);
+        let mut partner = Crab::new(WIN_W, START as u32);
+        partner.set_start_x(center + COOP_START_OFFSET);
+        partner.set_tint(self.theme.player_two());
+        self.partner = Some(partner);
+    }
+
+    /// Starts an online game once both sides are connected. The
+    /// board is built from the seed the host sent so both match.
+    fn start_online(&mut self, session: NetSession, seed: u64) {
+        self.new_game(GameMode::Online);
+        self.seed = seed;
+        self.rng = GameRng::new(seed);
+        self.net = Some(session);
+        self.listener = None;
+    }
+
+    /// Drops the connection, or stops waiting for one, and goes back to the main menu
+    fn leave_online(&mut self) {
+        self.listener = None;
+        self.net = None;
+        self.spectator = None;
+        self.main_menu.reset();
+        self.screen = Screen::MainMenu;
+    }
+
+    /// Checks for the other game turning up while in the lobby
+    fn poll_lobby(&mut self) -> io::Result<()> {
+        let hosted = match self.listener {
+            Some(ref listener) => net::accept(listener)?,
+            None => None,
+        };
+        if let Some(session) = hosted {
+            if let Some(seed) = session.seed() {
+                self.start_online(session, seed);
+            }
+            return Ok(());
+        }
+
+        let seed = match self.net {
+            Some(ref mut session) => {
+                session.receive()?;
+                session.seed()
+            }
+            None => None,
+        };
+        if let Some(seed) = seed {
+            if let Some(session) = self.net.take() {
+                self.start_online(session, seed);
+            }
+        }
+        Ok(())
+    }
+
+    /// Swaps actions with the other game and applies both players' actions
+    /// for the next frame. Returns false if the other game hasn't caught up
+    /// yet, in which case the board has to wait.
+    fn net_frame(&mut self) -> io::Result<bool> {
+        let inputs = match self.net {
+            Some(ref mut session) => {
+                session.send_actions(&mut self.pending_actions)?;
+                session.receive()?;
+                session.advance()
+            }
+            None => return Ok(true),
+        };
+
+        let inputs = match inputs {
+            Some(inputs) => inputs,
+            None => {
+                self.net_stall += 1;
+                return Ok(false);
+            }
+        };
+        self.net_stall = 0;
+        for action in inputs.host {
+            if self.player.get_lives() > 0 {
+                self.player.hop(action);
+            }
+        }
+        for action in inputs.guest {
+            self.partner_action(action);
+        }
+        Ok(true)
+    }
+
+    /// Starts or stops publishing frames for spectators to match the settings
+    fn apply_broadcast(&mut self) {
+        if !self.settings.broadcast {
+            self.broadcaster = None;
+        } else if self.broadcaster.is_none() {
+            match Broadcaster::new(SPECTATE_PORT) {
+                Ok(broadcaster) => self.broadcaster = Some(broadcaster),
+                Err(e) => println!("Could not broadcast on port {}: {}", SPECTATE_PORT, e),
+            }
+        }
+    }
+
+    /// Loads the theme named in the settings, with the chosen color mode over
+    /// it, and redraws everything with it. If its images can't be loaded the
+    /// current ones are kept.
+    fn apply_theme(&mut self, ctx: &mut Context) {
+        let theme = Theme::load(ctx, &self.settings.theme);
+        self.theme = self.settings.color_mode.apply(theme);
+        match Assets::load(ctx, &self.theme) {
+            Ok(assets) => self.assets = assets,
+            Err(e) => println!("Could not load the images for theme {}: {}", self.settings.theme, e),
+        }
+        self.traffic_layer = DrawBatch::new(self.assets.sprites.as_ref());
+        self.river_layer = DrawBatch::new(self.assets.sprites.as_ref());
+        set_background_color(ctx, self.theme.grass());
+        if let Some(ref mut partner) = self.partner {
+            partner.set_tint(self.theme.player_two());
+        }
+    }
+
+    /// Captures what spectators need to draw the board as it stands
+    fn spectator_frame(&self) -> SpectatorFrame {
+        let mut crabs = vec![self.player.get_state()];
+        if let Some(ref partner) = self.partner {
+            crabs.push(partner.get_state());
+        }
+        SpectatorFrame {
+            crabs: crabs,
+            lanes: self.lanes.iter().map(|lane| lane.get_state()).collect(),
+            river_lanes: self.river_lanes
+                .iter()
+                .map(|river_lane| river_lane.get_state())
+                .collect(),
+            cubbies: self.cubbies.get_state(),
+        }
+    }
+
+    /// Starts watching a game broadcast from another Crabber
+    fn start_spectating(&mut self, spectator: Spectator) {
+        self.new_game(GameMode::Arcade);
+        self.spectator = Some(spectator);
+        self.screen = Screen::Spectating;
+    }
+
+    /// Puts the board and crabs from a spectated game on screen
+    fn show_frame(&mut self, frame: &SpectatorFrame) {
+        let mut crabs = frame.crabs.iter();
+        if let Some(state) = crabs.next() {
+            self.player.set_state(state);
+        }
+        match crabs.next() {
+            Some(state) => {
+                if self.partner.is_none() {
+                    self.add_partner();
+                }
+                if let Some(ref mut partner) = self.partner {
+                    partner.set_state(state);
+                }
+            }
+            None => self.partner = None,
+        }
+        self.lanes = frame
+            .lanes
+            .iter()
+            .map(|lane| traffic::Lane::from_state(lane))
+            .collect();
+        self.river_lanes = frame
+            .river_lanes
+            .iter()
+            .map(|river_lane| river::RiverLane::from_state(river_lane))
+            .collect();
+        self.cubbies = Cubbies::from_state(&frame.cubbies);
+    }
+
+    /// Returns true while there is a game that could be saved,
+    /// including when it is paused behind a menu
+    fn game_in_progress(&self) -> bool {
+        match self.screen {
+            Screen::MainMenu | Screen::Lobby | Screen::Spectating | Screen::Scores => false,
+            Screen::Options => self.options_return != Screen::MainMenu,
+            Screen::Playing | Screen::Paused => true,
+        }
+    }
+
+    /// Stops the game and shows the pause menu
+    fn pause(&mut self) {
+        self.screen = Screen::Paused;
+        self.pause_menu.reset();
+        self.rewinding = false;
+    }
+
+    /// Writes the current game to disk so it can be continued,
+    /// practice runs don't score, versus races are too short to be worth
+    /// saving and online games can't be picked up without the other player
+    fn write_save(&mut self, ctx: &mut Context) {
+        let saved_mode = match self.mode {
+            GameMode::Arcade | GameMode::TwoPlayer | GameMode::CoOp => true,
+            GameMode::Practice | GameMode::Versus | GameMode::Online => false,
+        };
+        if self.game_in_progress() && saved_mode {
+            // A dying crab is still where it died, so finish the death
+            // first or it would come back alive in the road or river
+            if self.death_frames > 0 {
+                self.death_frames = 0;
+                self.respawn_crabs();
+                // A game that has just ended has nothing left to continue
+                if self.out_of_lives() {
+                    self.game_over(ctx);
+                    return;
+                }
+            }
+            if let Err(e) = self.save_game().write(ctx) {
+                println!("Could not save game: {}", e);
+            }
+        }
+    }
+
+    /// Checks the player's final score against its replay on another
+    /// thread, which then sends it to the leaderboard server if it can be
+    /// reached. Scores that check out come back to poll_checked_scores.
+    fn record_score(&mut self) {
+        if self.invincible_used {
+            println!("Invincibility was used, not recording the score");
+            return;
+        }
+        let entry = ScoreEntry {
+            name: self.settings.player_name.clone(),
+            score: self.player.get_score(),
+            seed: self.seed,
+            level: self.level,
+            replay: self.replay.clone(),
+        };
+        let address = self.settings.leaderboard_address.clone();
+        leaderboard::record_score_in_background(address, entry, self.score_checker.clone());
+    }
+
+    /// Adds any scores that have finished being checked to the local high score file
+    fn poll_checked_scores(&mut self, ctx: &mut Context) {
+        while let Ok(entry) = self.checked_scores.try_recv() {
+            let mut local = Leaderboard::load(ctx);
+            if local.add(entry) {
+                if let Err(e) = local.save(ctx) {
+                    println!("Could not save high scores: {}", e);
+                }
+            }
+        }
+    }
+
+    /// Shows the local high scores straight away and asks the leaderboard
+    /// for its top scores, which replace them once they arrive
+    fn show_scores(&mut self, ctx: &mut Context) {
+        self.high_scores = Leaderboard::load(ctx).entries().to_vec();
+        self.scores_title = "Local Scores";
+        let address = self.settings.leaderboard_address.clone();
+        self.scores_request = Some(leaderboard::fetch_scores_in_background(address));
+        self.screen = Screen::Scores;
+    }
+
+    /// Puts the leaderboard's top scores on screen if they have arrived
+    fn poll_scores(&mut self) {
+        let received = match self.scores_request {
+            Some(ref receiver) => match receiver.try_recv() {
+                Ok(result) => result,
+                Err(TryRecvError::Empty) => return,
+                Err(TryRecvError::Disconnected) => {
+                    Err(io::Error::new(io::ErrorKind::Other, "request was dropped"))
+                }
+            },
+            None => return,
+        };
+        self.scores_request = None;
+        match received {
+            Ok(scores) => {
+                self.high_scores = scores;
+                self.scores_title = "Top Scores";
+            }
+            Err(e) => println!("Could not reach the leaderboard, showing local scores: {}", e),
+        }
+    }
+
+    /// Moves the music volume one step up or down and remembers it
+    fn change_music_volume(&mut self, ctx: &mut Context, up: bool) {
+        let volume = step_volume(self.settings.music_volume, up);
+        self.settings.music_volume = volume;
+        self.sounds.set_music_volume(volume);
+        self.save_settings(ctx);
+    }
+
+    /// Moves the sound effects volume one step up or down and remembers it
+    fn change_effects_volume(&mut self, ctx: &mut Context, up: bool) {
+        let volume = step_volume(self.settings.effects_volume, up);
+        self.settings.effects_volume = volume;
+        self.sounds.set_effects_volume(volume);
+        self.save_settings(ctx);
+    }
+
+    /// Switches to the next or previous theme and remembers it
+    fn change_theme(&mut self, ctx: &mut Context, forward: bool) {
+        self.settings.theme = step_theme(&self.theme_names, &self.settings.theme, forward);
+        self.apply_theme(ctx);
+        self.save_settings(ctx);
+    }
+
+    /// Switches to the next or previous color mode and remembers it
+    fn change_color_mode(&mut self, ctx: &mut Context, forward: bool) {
+        self.settings.color_mode = self.settings.color_mode.step(forward);
+        self.apply_theme(ctx);
+        self.save_settings(ctx);
+    }
+
+    /// Writes the key bindings to the settings file
+    fn save_key_map(&mut self, ctx: &mut Context) {
+        self.settings.key_bindings = self.key_map.to_names();
+        self.save_settings(ctx);
+    }
 
-                //Update the crab's speed
-                self.player.update();
-            }
-            // or with vehicles else
-            if self.player.get_bottom_edge() > MID_ROW as f32 * SQUARE_SIZE
-                && self.player.get_bottom_edge() < WIN_H as f32 - SQUARE_SIZE * 2.0
-            {
-                'outerCar: for i in 0..self.lanes.len() {
-                    for j in 0..self.lanes[i].vehicles.len() {
-                        if self.player.get_left_edge() >= self.lanes[i].vehicles[j].get_right_edge()
-                        {
-                            continue;
-                        }
-
-                        if self.player.get_right_edge() <= self.lanes[i].vehicles[j].get_left_edge()
-                        {
-                            continue;
-                        }
-
-                        if self.player.get_bottom_edge() <= self.lanes[i].vehicles[j].get_top_edge()
-                        {
-                            continue;
-                        }
-
-                        if self.player.get_top_edge() >= self.lanes[i].vehicles[j].get_bottom_edge()
-                        {
-                            continue;
-                        }
-
-                        self.player.lose_life();
-                        break 'outerCar;
+    /// Opens the options screen, returning to the current screen when done
+    fn open_options(&mut self) {
+        self.options_menu.reset();
+        self.options_return = self.screen;
+        self.screen = Screen::Options;
+    }
+
+    /// Handles an action on the main menu
+    fn main_menu_action(&mut self, ctx: &mut Context, action: Action) {
+        match self.main_menu.handle(action) {
+            Some(MenuCommand::Chosen(MenuChoice::NewGame)) => self.new_game(GameMode::Arcade),
+            Some(MenuCommand::Chosen(MenuChoice::TwoPlayers)) => {
+                self.new_game(GameMode::TwoPlayer)
+            }
+            Some(MenuCommand::Chosen(MenuChoice::CoOp)) => self.new_game(GameMode::CoOp),
+            Some(MenuCommand::Chosen(MenuChoice::Versus)) => self.new_game(GameMode::Versus),
+            Some(MenuCommand::Chosen(MenuChoice::Practice)) => self.new_game(GameMode::Practice),
+            Some(MenuCommand::Chosen(MenuChoice::Continue)) if SaveGame::exists(ctx) => {
+                match SaveGame::load(ctx) {
+                    Ok(save) => {
+                        self.new_game(GameMode::Arcade);
+                        self.load_game(save);
+                    }
+                    Err(e) => println!("Could not load save game: {}", e),
+                }
+            }
+            Some(MenuCommand::Chosen(MenuChoice::Host)) => match net::listen(NET_PORT) {
+                Ok(listener) => {
+                    self.listener = Some(listener);
+                    self.screen = Screen::Lobby;
+                }
+                Err(e) => println!("Could not host a game: {}", e),
+            },
+            Some(MenuCommand::Chosen(MenuChoice::Join)) => {
+                match net::connect(&self.settings.join_address) {
+                    Ok(session) => {
+                        self.net = Some(session);
+                        self.screen = Screen::Lobby;
                     }
+                    Err(e) => println!("Could not join {}: {}", self.settings.join_address, e),
+                }
+            }
+            Some(MenuCommand::Chosen(MenuChoice::Spectate)) => {
+                match Spectator::connect(&self.settings.spectate_address) {
+                    Ok(spectator) => self.start_spectating(spectator),
+                    Err(e) => println!(
+                        "Could not spectate {}: {}",
+                        self.settings.spectate_address, e
+                    ),
                 }
             }
+            Some(MenuCommand::Chosen(MenuChoice::Options)) => self.open_options(),
+            Some(MenuCommand::Chosen(MenuChoice::Scores)) => self.show_scores(ctx),
+            _ => {}
+        }
+    }
 
-            // Check for collisions with cubbies
-            if self.player.get_bottom_edge() < END
-                && self.player.get_left_edge() % (SQUARE_SIZE * 4.0) < SQUARE_SIZE * 2.0
-            {
-                self.player.lose_life();
-            }
-        }
-
-        // Check for occupied cubbie
-        if self.player.get_bottom_edge() < END
-            && self.player.get_left_edge() % (SQUARE_SIZE * 4.0) >= SQUARE_SIZE * 2.0
-        {
-            let i = (self.player.get_left_edge() / (SQUARE_SIZE * 4.0)) as usize;
-            if self.cubbies.get_is_occupied(i) == false {
-                // Get points for it
-                self.player.add_to_score(500);
-                // Set occupied flag to true
-                self.cubbies.set_is_occupied(i);
-                // Reset board
-                timer::sleep(Duration::from_secs(1));
-                self.player.restart_x();
-                self.player.restart_y();
-            } else {
-                self.player.occupied_cubbie_override();
+    /// Handles an action while the pause menu is open
+    fn pause_menu_action(&mut self, ctx: &mut Context, action: Action) {
+        match self.pause_menu.handle(action) {
+            Some(MenuCommand::Back) | Some(MenuCommand::Chosen(MenuChoice::Resume)) => {
+                self.screen = Screen::Playing
+            }
+            // Restarting one side of an online game alone would split the boards
+            Some(MenuCommand::Chosen(MenuChoice::Restart)) if self.mode != GameMode::Online => {
+                let mode = self.mode;
+                self.new_game(mode);
+            }
+            Some(MenuCommand::Chosen(MenuChoice::Options)) => self.open_options(),
+            Some(MenuCommand::Chosen(MenuChoice::QuitToMenu)) => {
+                self.write_save(ctx);
+                self.leave_online();
+            }
+            _ => {}
+        }
+    }
+
+    /// Handles an action on the options screen
+    fn options_menu_action(&mut self, ctx: &mut Context, action: Action) {
+        match self.options_menu.handle(action) {
+            Some(MenuCommand::Adjusted(MenuChoice::MusicVolume, up)) => {
+                self.change_music_volume(ctx, up)
+            }
+            Some(MenuCommand::Adjusted(MenuChoice::EffectsVolume, up)) => {
+                self.change_effects_volume(ctx, up)
+            }
+            Some(MenuCommand::Adjusted(MenuChoice::Broadcast, _))
+            | Some(MenuCommand::Chosen(MenuChoice::Broadcast)) => {
+                self.settings.broadcast = !self.settings.broadcast;
+                self.apply_broadcast();
+                self.save_settings(ctx);
+            }
+            Some(MenuCommand::Adjusted(MenuChoice::Theme, forward)) => {
+                self.change_theme(ctx, forward)
+            }
+            Some(MenuCommand::Chosen(MenuChoice::Theme)) => self.change_theme(ctx, true),
+            Some(MenuCommand::Adjusted(MenuChoice::ColorMode, forward)) => {
+                self.change_color_mode(ctx, forward)
+            }
+            Some(MenuCommand::Chosen(MenuChoice::ColorMode)) => {
+                self.change_color_mode(ctx, true)
+            }
+            // The next key pressed gets bound to this action
+            Some(MenuCommand::Chosen(MenuChoice::Binding(binding))) => {
+                self.rebinding = Some(binding)
+            }
+            Some(MenuCommand::Chosen(MenuChoice::ResetKeys)) => {
+                self.key_map = KeyMap::default();
+                self.save_key_map(ctx);
+            }
+            Some(MenuCommand::Back) => self.screen = self.options_return,
+            _ => {}
+        }
+    }
+
+    /// Handles a key press that isn't bound to any action
+    fn unbound_key(&mut self, ctx: &mut Context, keycode: Keycode) {
+        match keycode {
+            // Volume can be changed from anywhere
+            Keycode::LeftBracket | Keycode::RightBracket => {
+                self.change_music_volume(ctx, keycode == Keycode::RightBracket)
+            }
+            Keycode::Minus | Keycode::Equals => {
+                self.change_effects_volume(ctx, keycode == Keycode::Equals)
+            }
+            Keycode::F3 => self.debug.toggle(),
+            Keycode::F4 => self.toggle_invincible(),
+            Keycode::R if self.screen == Screen::Playing => self.rewinding = self.mode == GameMode::Practice,
+            Keycode::Backspace if self.screen == Screen::Options => {
+                if let Some(MenuChoice::Binding(binding)) = self.options_menu.selected() {
+                    self.key_map.reset(binding);
+                    self.save_key_map(ctx);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    /// Carries out an action from the keyboard or a controller on the current screen
+    fn perform_action(&mut self, ctx: &mut Context, action: Action) {
+        match self.screen {
+            Screen::MainMenu => self.main_menu_action(ctx, action),
+            Screen::Paused => self.pause_menu_action(ctx, action),
+            Screen::Options => self.options_menu_action(ctx, action),
+            // Backing out of the lobby or a spectated game drops the connection
+            Screen::Lobby | Screen::Spectating => match action {
+                Action::Pause | Action::Confirm => self.leave_online(),
+                _ => {}
+            },
+            Screen::Scores => match action {
+                Action::Pause | Action::Confirm => self.screen = Screen::MainMenu,
+                _ => {}
+            },
+            Screen::Playing => match action {
+                Action::Pause => self.pause(),
+                // Nobody moves while a crab is dying or the board is held
+                _ if self.board_held() => {}
+                // Online moves wait to be sent so both boards make them on the same frame
+                _ if self.net.is_some() => self.pending_actions.push(action),
+                // A co-op crab out of lives can't be moved
+                _ if self.player.get_lives() > 0 => {
+                    // Kept so the final score can be checked by playing the game back
+                    self.replay.record(action);
+                    self.player.hop(action)
+                }
+                _ => {}
+            },
+        }
+    }
+
+    /// Moves the second crab in co-op
+    fn partner_action(&mut self, action: Action) {
+        if self.board_held() {
+            return;
+        }
+        if let Some(ref mut partner) = self.partner {
+            if partner.get_lives() > 0 {
+                partner.hop(action);
+            }
+        }
+    }
+
+    /// Carries out an action from the controller, which
+    /// moves the second crab in co-op and the first otherwise
+    fn gamepad_action(&mut self, ctx: &mut Context, action: Action) {
+        let moving = match action {
+            Action::Up | Action::Down | Action::Left | Action::Right => true,
+            Action::Confirm | Action::Pause => false,
+        };
+        let local_partner = self.partner.is_some() && self.net.is_none();
+        if moving && self.screen == Screen::Playing && local_partner {
+            self.partner_action(action);
+        } else {
+            self.perform_action(ctx, action);
+        }
+    }
+
+    /// Captures everything needed to resume the current game later
+    fn save_game(&self) -> SaveGame {
+        SaveGame {
+            crab: self.player.get_state(),
+            lanes: self.lanes.iter().map(|lane| lane.get_state()).collect(),
+            river_lanes: self.river_lanes
+                .iter()
+                .map(|river_lane| river_lane.get_state())
+                .collect(),
+            cubbies: self.cubbies.get_state(),
+            rng: self.rng.clone(),
+            turns: self.turns.clone(),
+            partner: self.partner.as_ref().map(|partner| partner.get_state()),
+            seed: self.seed,
+            level: self.level,
+            replay: self.replay.clone(),
+        }
+    }
+
+    /// Replaces the current game with a previously saved one
+    fn load_game(&mut self, save: SaveGame) {
+        // Set the start columns before putting the crabs back
+        if let Some(ref state) = save.partner {
+            self.mode = GameMode::CoOp;
+            self.add_partner();
+            if let Some(ref mut partner) = self.partner {
+                partner.set_state(state);
+            }
+        }
+        self.player.set_state(&save.crab);
+        self.lanes = save.lanes
+            .iter()
+            .map(|lane| traffic::Lane::from_state(lane))
+            .collect();
+        self.lane_modifier = LANE_MODIFIER + self.lanes.len() as f32;
+        self.river_lanes = save.river_lanes
+            .iter()
+            .map(|river_lane| river::RiverLane::from_state(river_lane))
+            .collect();
+        self.river_lane_modifier = RIVER_LANE_MODIFIER + self.river_lanes.len() as f32;
+        self.cubbies = Cubbies::from_state(&save.cubbies);
+        self.rng = save.rng;
+        self.seed = save.seed;
+        self.level = save.level.max(1);
+        self.replay = save.replay;
+        if save.turns.is_some() {
+            self.mode = GameMode::TwoPlayer;
+            self.turns = save.turns;
+        }
+    }
+
+    /// Turns invincibility on or off. A game that has used it
+    /// at any point doesn't get its score recorded.
+    fn toggle_invincible(&mut self) {
+        self.debug.toggle_invincible();
+        if self.debug.is_invincible() && self.game_in_progress() {
+            self.invincible_used = true;
+        }
+    }
+
+    /// Moves everything on by one frame
+    fn step(&mut self, _ctx: &mut Context) -> GameResult<()> {
+        // A direction held on the controller repeats like a held key
+        if let Some(action) = self.gamepad.update() {
+            if self.rebinding.is_none() {
+                self.gamepad_action(_ctx, action);
+            }
+        }
+
+        if self.game_in_progress() {
+            self.sounds.play_music(MusicTrack::Game);
+        } else {
+            self.sounds.play_music(MusicTrack::Menu);
+        }
+
+        self.poll_checked_scores(_ctx);
+        if self.screen == Screen::Scores {
+            self.poll_scores();
+        }
+
+        if self.screen == Screen::Lobby {
+            if let Err(e) = self.poll_lobby() {
+                println!("Could not start online game: {}", e);
+                self.leave_online();
+            }
+        }
+
+        // Spectated games are only ever drawn, never updated here
+        if self.screen == Screen::Spectating {
+            let received = match self.spectator {
+                Some(ref mut spectator) => spectator.receive(),
+                None => Ok(None),
+            };
+            match received {
+                Ok(Some(frame)) => self.show_frame(&frame),
+                Ok(None) => {}
+                Err(e) => {
+                    println!("Lost connection to the game being spectated: {}", e);
+                    self.leave_online();
+                }
+            }
+        }
+
+        // Lanes and timers only move while actually playing
+        if self.screen != Screen::Playing {
+            return Ok(());
+        }
+
+        // Let anyone spectating see the board as it stands
+        if self.broadcaster.is_some() {
+            let frame = self.spectator_frame();
+            if let Some(ref mut broadcaster) = self.broadcaster {
+                broadcaster.publish(&frame);
+            }
+        }
+
+        // Particles keep moving even while the board is held still
+        self.particles.update();
+
+        // Hold the board still while the next player gets ready
+        if self.turn_banner > 0 {
+            self.turn_banner -= 1;
+            return Ok(());
+        }
+
+        // Hold the board still while a crab's death plays out, then respawn it
+        // and end the game if nobody has lives left. These frames and the
+        // ones below aren't played, so replays never see them.
+        if self.death_frames > 0 {
+            self.death_frames -= 1;
+            if self.death_frames == 0 {
+                self.respawn_crabs();
+                if self.out_of_lives() {
+                    self.game_over(_ctx);
+                }
+                self.dispatch_events();
+            }
+            return Ok(());
+        }
+
+        // Hold the board still while a message is shown
+        if let Some(mut announcement) = self.announcement.take() {
+            announcement.frames = announcement.frames.saturating_sub(1);
+            if announcement.frames > 0 {
+                self.announcement = Some(announcement);
+            } else if announcement.then_menu {
+                self.main_menu.reset();
+                self.screen = Screen::MainMenu;
+            }
+            return Ok(());
+        }
+
+        // Hold the board still for a moment after a cubby is filled
+        if self.hold_frames > 0 {
+            self.hold_frames -= 1;
+            return Ok(());
+        }
+
+        // Step the board backwards one frame while rewind is held
+        if self.mode == GameMode::Practice && self.rewinding {
+            if let Some(snapshot) = self.rewind.pop() {
+                self.restore_snapshot(&snapshot);
+            }
+            return Ok(());
+        }
+
+        // Online games only move on once both players' actions are in
+        match self.net_frame() {
+            Ok(true) => {}
+            Ok(false) => return Ok(()),
+            Err(e) => {
+                println!("Lost connection to the other player: {}", e);
+                self.leave_online();
+                return Ok(());
+            }
+        }
+
+        // Replays count the frames actually played, the same way they are played back
+        self.replay.advance();
+
+        // Create new lanes
+        if (self.lanes.len() as u32) < NUM_LANE {
+            self.lanes
+                .push(traffic::Lane::construct(self.lane_modifier, &mut self.rng));
+            self.lane_modifier += 1.0;
+        }
+
+        // Both sides of an online game have to die the same way to stay in step
+        let invincible = self.debug.is_invincible() && self.mode != GameMode::Online;
+
+        // Every crab still in the game shares the same board
+        let mut crabs = vec![&mut self.player];
+        if let Some(ref mut partner) = self.partner {
+            crabs.push(partner);
+        }
+        for (player, crab) in (1..).zip(crabs) {
+            crab.animate();
+
+            // A co-op crab out of lives sits out until the game ends
+            if crab.get_lives() <= 0 {
+                continue;
+            }
+
+            crab.set_invincible(invincible);
+            collisions::check_collisions(crab, &mut self.lanes, &mut self.river_lanes);
+
+            // Keep the crab's events ahead of anything that happens next
+            self.events.extend(crab.take_events());
+
+            // Check for occupied cubbie
+            if let Some(i) = collisions::enter_cubbie(crab, &mut self.cubbies) {
+                // Once claimed the cubbie blocks the other crab too
+                self.cubbies.set_owner(i, player);
+                self.events.push(GameEvent::CubbyFilled(i));
+                let (x, y) = self.cubbies.get_center(i);
+                self.particles.add(particles::confetti(x, y));
+                self.hold_frames = CUBBY_HOLD_FRAMES;
             }
         }
 
@@ -199,8 +1285,10 @@ impl event::EventHandler for MainState {
 
         //Create new river lanes
         if (self.river_lanes.len() as u32) < NUM_LOG {
-            self.river_lanes
-                .push(river::RiverLane::construct(self.river_lane_modifier));
+            self.river_lanes.push(river::RiverLane::construct(
+                self.river_lane_modifier,
+                &mut self.rng,
+            ));
             self.river_lane_modifier += 1.0;
         }
 
@@ -208,116 +1296,245 @@ impl event::EventHandler for MainState {
         for river_lane in &mut self.river_lanes {
             river_lane.update_river_transports_in_river_lane();
         }
+        self.emit_trails();
 
-        //Check for game over
-        if self.player.get_lives() <= 0 || self.cubbies.get_filled_cubbies() == WINNING_CUBBIES {
-            let victory = self.cubbies.get_filled_cubbies() == WINNING_CUBBIES;
+        // The first crab to claim enough cubbies wins the race
+        if self.mode == GameMode::Versus {
+            let winner = (1..3).find(|player| self.cubbies.get_claimed(*player) >= VERSUS_CUBBIES);
+            if let Some(winner) = winner {
+                self.events.push(GameEvent::LevelCleared);
+                self.dispatch_events();
+                self.announce(format! {"PLAYER {} WINS!", winner}, true);
+                return Ok(());
+            }
+        }
+
+        // Filling every cubbie moves on to the next level. Running out of
+        // lives is only checked once the last death has played out.
+        if self.cubbies.get_filled_cubbies() == WINNING_CUBBIES {
             //self.cubbies.reset_cubbies();
             self.cubbies = Cubbies::construct();
+            self.events.push(GameEvent::LevelCleared);
+            self.level += 1;
+            self.announce("Win!".to_string(), false);
+        }
 
-            //Clear screen, optional
-            graphics::clear(_ctx);
-
-            if victory {
-                let font = graphics::Font::new(_ctx, "/game_over.ttf", 48).unwrap();
-                let text = graphics::Text::new(_ctx, "Win!", &font)?;
-
-                let center: f32 = WIN_W as f32 / 2.0 - *&text.width() as f32 / 2.0;
-
-                let dest_point = graphics::Point2::new(center, WIN_H as f32 / 2.0);
-                graphics::draw(_ctx, &text, dest_point, 0.0)?;
-            } else {
-                self.player.set_lives();
-                self.player.reset_score();
-                //Game over has a scalable center, text should always be in center regardless of dimensions
-                let center: f32 = WIN_W as f32 / 2.0 - *&self.game_over_man.width() as f32 / 2.0;
-
-                let dest_point = graphics::Point2::new(center, WIN_H as f32 / 2.0);
-                graphics::draw(_ctx, &self.game_over_man, dest_point, 0.0)?;
-            }
-
-            graphics::present(_ctx);
-            timer::sleep(Duration::from_secs(2));
+        // Crabs that died stay where they were while their death plays out
+        let partner_died = self.partner
+            .as_mut()
+            .map_or(false, |partner| partner.get_life_lost());
+        if self.player.get_life_lost() || partner_died {
+            self.death_frames = DEATH_ANIMATION_FRAMES;
+            self.splash_drowned_crabs();
         }
 
-        //Take a life
-        if self.player.get_life_lost() == true {
-            self.player.set_life_lost();
-            timer::sleep(Duration::from_secs(1));
-            self.player.restart_x();
-            self.player.restart_y();
+        self.dispatch_events();
+
+        // Remember this frame so practice runs can be rewound,
+        // but never to a crab that is in the middle of dying
+        if self.mode == GameMode::Practice && self.death_frames == 0 {
+            let snapshot = self.take_snapshot();
+            self.rewind.push(snapshot);
         }
 
         Ok(())
     }
+}
+
+impl event::EventHandler for MainState {
+    fn update(&mut self, ctx: &mut Context) -> GameResult<()> {
+        let started = Instant::now();
+        let result = self.step(ctx);
+        self.debug.record_update(started.elapsed());
+        result
+    }
 
     fn draw(&mut self, ctx: &mut Context) -> GameResult<()> {
         graphics::clear(ctx);
 
-        if self.main_menu {
-            let mut draw_main = Menu {};
-            draw_main.draw(ctx, self.selection)?;
-        } else {
-            //Draw background
-            self.road.draw(ctx)?;
-            self.river.draw(ctx)?;
-            self.cubbies.draw(ctx)?;
+        if self.game_in_progress() || self.screen == Screen::Spectating {
+            self.draw_board(ctx)?;
+            self.particles.draw(ctx, &self.assets.pixel)?;
+            self.draw_crabs(ctx)?;
+            self.draw_hud(ctx)?;
 
-            //Draw our lanes
-            for lane in &mut self.lanes {
-                lane.draw_vehicles_in_lane(ctx)?;
+            let mut crabs = vec![&self.player];
+            if let Some(ref partner) = self.partner {
+                crabs.push(partner);
             }
+            self.debug
+                .draw(ctx, &mut self.assets, &crabs, &self.lanes, &self.river_lanes)?;
+        }
 
-            //Draw our river lanes
-            for river_lane in &mut self.river_lanes {
-                river_lane.draw_river_transports_in_river_lane(ctx)?;
+        // Say what killed the crab while it dies
+        if self.death_frames > 0 && self.screen == Screen::Playing {
+            self.draw_death_message(ctx)?;
+        }
+
+        // Show a level cleared, race won or game over while the board is held for it
+        if self.screen == Screen::Playing {
+            if let Some(ref announcement) = self.announcement {
+                self.assets.draw_centered(
+                    ctx,
+                    FontSize::Large,
+                    &announcement.text,
+                    WIN_H as f32 / 2.0,
+                )?;
             }
+        }
 
-            self.player.draw(ctx)?;
+        // Say whose turn it is while the board is held for them
+        if self.turn_banner > 0 && self.screen == Screen::Playing {
+            if let Some(ref turns) = self.turns {
+                let banner = format! {"PLAYER {}", turns.current()};
+                self.assets
+                    .draw_centered(ctx, FontSize::Large, &banner, WIN_H as f32 / 2.0)?;
+            }
+        }
 
-            //Draw the lives in the bottom left
-            let lives = format! {"Lives: {}", self.player.get_lives()};
-            let font_smaller = graphics::Font::new(ctx, "/game_over.ttf", 16).unwrap();
-            let lives_text = graphics::Text::new(ctx, &lives, &font_smaller)?;
-            let dest_point = graphics::Point2::new(0 as f32, WIN_H as f32 - SQUARE_SIZE);
-            graphics::draw(ctx, &lives_text, dest_point, 0.0)?;
+        // Let the player know why the board has stopped
+        if self.net_stall > NET_STALL_FRAMES && self.screen == Screen::Playing {
+            self.assets.draw_centered(
+                ctx,
+                FontSize::Medium,
+                "Waiting for the other player...",
+                WIN_H as f32 / 2.0,
+            )?;
+        }
 
-            //Draw the lives in the bottom left
-            let score = format! {"Score: {}", self.player.get_score()};
-            let score_text = graphics::Text::new(ctx, &score, &font_smaller)?;
-            let score_width = score_text.width() as f32;
-            let dest_point =
-                graphics::Point2::new(WIN_W as f32 - score_width, WIN_H as f32 - SQUARE_SIZE);
-            graphics::draw(ctx, &score_text, dest_point, 0.0)?;
+        match self.screen {
+            Screen::MainMenu => {
+                let mut draw_main = Menu {};
+                draw_main.draw(ctx, &mut self.assets, &self.main_menu)?;
+            }
+            Screen::Lobby => {
+                let message = if self.listener.is_some() {
+                    format!("Waiting for a player on port {}...", NET_PORT)
+                } else {
+                    format!("Connecting to {}...", self.settings.join_address)
+                };
+                let mut draw_lobby = Lobby {};
+                draw_lobby.draw(ctx, &mut self.assets, &message)?;
+            }
+            Screen::Paused => {
+                let mut draw_pause = PauseMenu {};
+                draw_pause.draw(ctx, &mut self.assets, &self.pause_menu)?;
+            }
+            Screen::Options => {
+                let mut draw_options = OptionsMenu {};
+                menu::refresh_options_menu(
+                    &mut self.options_menu,
+                    &self.settings,
+                    &self.key_map,
+                    self.rebinding,
+                );
+                draw_options.draw(ctx, &mut self.assets, &self.options_menu)?;
+            }
+            Screen::Spectating => {
+                let label = "Spectating - press Pause to stop";
+                self.assets.draw_centered(ctx, FontSize::Small, label, 0.0)?;
+            }
+            Screen::Scores => {
+                let mut draw_scores = HighScores {};
+                draw_scores.draw(ctx, &mut self.assets, self.scores_title, &self.high_scores)?;
+            }
+            Screen::Playing => {}
         }
 
         graphics::present(ctx);
         Ok(())
     }
 
-    fn key_down_event(&mut self, _ctx: &mut ggez::Context, keycode: Keycode, _: Mod, _: bool) {
-        if self.main_menu {
-            match keycode {
-                Keycode::Down => (self.selection = 1),
-                Keycode::Up => (self.selection = 0),
-                Keycode::Return => {
-                    if self.selection == 0 {
-                        self.main_menu = false
-                    }
-                }
-                _ => {}
+    fn key_down_event(&mut self, ctx: &mut ggez::Context, keycode: Keycode, _: Mod, _: bool) {
+        // While rebinding the next key goes to the action being rebound,
+        // Escape cancels so the player can't get stuck
+        if let Some(action) = self.rebinding.take() {
+            if keycode != Keycode::Escape {
+                self.key_map.bind(action, keycode);
+                self.save_key_map(ctx);
             }
-        } else {
-            match keycode {
-                Keycode::Up => self.player.move_up(),
-                Keycode::Down => self.player.move_down(),
-                Keycode::Right => self.player.move_right(),
-                Keycode::Left => self.player.move_left(),
+            return;
+        }
 
-                _ => {}
+        // The second crab's keys come first while it is on the board
+        if self.screen == Screen::Playing && self.partner.is_some() && self.net.is_none() {
+            if let Some(action) = self.partner_keys.action_for(keycode) {
+                self.partner_action(action);
+                return;
+            }
+        }
+
+        let action = match self.key_map.action_for(keycode) {
+            Some(action) => action,
+            None => {
+                self.unbound_key(ctx, keycode);
+                return;
+            }
+        };
+
+        self.perform_action(ctx, action);
+    }
+
+    fn key_up_event(&mut self, _ctx: &mut ggez::Context, keycode: Keycode, _: Mod, _: bool) {
+        if keycode == Keycode::R {
+            self.rewinding = false;
+        }
+    }
+
+    fn controller_button_down_event(&mut self, ctx: &mut Context, btn: Button, _: i32) {
+        // Rebinding only takes keys
+        if self.rebinding.is_some() {
+            return;
+        }
+
+        // X works like holding R to rewind
+        if btn == Button::X && self.screen == Screen::Playing {
+            self.rewinding = self.mode == GameMode::Practice;
+            return;
+        }
+
+        let action = match button_action(btn, self.screen != Screen::Playing) {
+            Some(action) => action,
+            None => return,
+        };
+
+        // The D-pad goes through the gamepad so it repeats when held
+        let action = match action {
+            Action::Up | Action::Down | Action::Left | Action::Right => self.gamepad.dpad_down(action),
+            _ => action,
+        };
+
+        self.gamepad_action(ctx, action);
+    }
+
+    fn controller_button_up_event(&mut self, _ctx: &mut Context, btn: Button, _: i32) {
+        if btn == Button::X {
+            self.rewinding = false;
+        } else if let Some(action) = button_action(btn, false) {
+            self.gamepad.dpad_up(action);
+        }
+    }
+
+    fn controller_axis_event(&mut self, ctx: &mut Context, axis: Axis, value: i16, _: i32) {
+        if let Some(action) = self.gamepad.axis(axis, value) {
+            if self.rebinding.is_none() {
+                self.gamepad_action(ctx, action);
             }
         }
     }
+
+    fn focus_event(&mut self, _ctx: &mut Context, gained: bool) {
+        // Don't let the crab get run over while the window is in the background
+        if !gained && self.screen == Screen::Playing {
+            self.pause();
+        }
+    }
+
+    fn quit_event(&mut self, ctx: &mut Context) -> bool {
+        // Save an in-progress game so it can be continued from the menu
+        self.write_save(ctx);
+        false
+    }
 }
 
 pub fn main() {
@@ -325,8 +1542,21 @@ pub fn main() {
     c.window_setup.title = "C R A B B E R".to_string();
     c.window_mode.width = WIN_W;
     c.window_mode.height = WIN_H;
-    let ctx = &mut Context::load_from_conf("crabber", "ggez", c).unwrap();
+    // Blocked on ggez: 0.4 always opens the audio device along with the
+    // window, so the game can't start without one yet. ggez 0.5 can turn
+    // audio off with conf.modules.audio = false, and Sounds already copes
+    // with having nothing loaded.
+    let ctx = &mut match Context::load_from_conf("crabber", "ggez", c) {
+        Ok(ctx) => ctx,
+        Err(GameError::AudioError(e)) => {
+            println!("Could not open an audio device: {}", e);
+            process::exit(1);
+        }
+        Err(e) => {
+            println!("Could not start Crabber: {}", e);
+            process::exit(1);
+        }
+    };
     let state = &mut MainState::new(ctx).unwrap();
-    set_background_color(ctx, GRASS);
     event::run(ctx, state).unwrap();
 }
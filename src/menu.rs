@@ -0,0 +1,437 @@
+/*
+Copyright (c) 2018 Matt Carnovale, Julie Rutherford-Fields, Joshua Sander
+This work is available under the "MIT License”.
+Please see the file LICENSE in this distribution
+for license terms.
+*/
+
+use assets::{Assets, FontSize};
+use constants::{SQUARE_SIZE, WIN_H, WIN_W};
+use controls::{Action, KeyMap, ACTIONS};
+use ggez::graphics;
+use ggez::{Context, GameResult};
+use settings::Settings;
+
+/// Everything that can be picked from one of the game's menus
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum MenuChoice {
+    NewGame,
+    TwoPlayers,
+    CoOp,
+    Versus,
+    Host,
+    Join,
+    Spectate,
+    Practice,
+    Continue,
+    Options,
+    Scores,
+    Resume,
+    Restart,
+    QuitToMenu,
+    MusicVolume,
+    EffectsVolume,
+    Broadcast,
+    Theme,
+    ColorMode,
+    Binding(Action),
+    ResetKeys,
+}
+
+/// What happens when a menu item is confirmed
+pub enum MenuItemKind<T> {
+    /// Hands the choice back to the game
+    Choice(T),
+    /// Opens another menu in place of this one
+    Submenu(MenuList<T>),
+    /// Goes back out of this menu
+    Back,
+}
+
+/// One labeled entry in a menu
+pub struct MenuItem<T> {
+    label: String,
+    kind: MenuItemKind<T>,
+}
+
+impl<T> MenuItem<T> {
+    /// Creates an item that hands back a choice
+    pub fn choice(label: &str, choice: T) -> MenuItem<T> {
+        MenuItem {
+            label: label.to_string(),
+            kind: MenuItemKind::Choice(choice),
+        }
+    }
+
+    /// Creates an item that opens a submenu
+    pub fn submenu(label: &str, menu: MenuList<T>) -> MenuItem<T> {
+        MenuItem {
+            label: label.to_string(),
+            kind: MenuItemKind::Submenu(menu),
+        }
+    }
+
+    /// Creates an item that goes back out of the menu
+    pub fn back(label: &str) -> MenuItem<T> {
+        MenuItem {
+            label: label.to_string(),
+            kind: MenuItemKind::Back,
+        }
+    }
+}
+
+/// What a menu asks the game to do in response to an action
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum MenuCommand<T> {
+    /// The item with this choice was confirmed
+    Chosen(T),
+    /// Left or right was pressed on this choice, true for right
+    Adjusted(T, bool),
+    /// The player backed out of the top level of the menu
+    Back,
+}
+
+/// A vertical list of items navigated with the Up and Down actions.
+/// Selection wraps around at either end, and a submenu replaces the
+/// list until the player backs out of it.
+pub struct MenuList<T> {
+    title: Option<String>,
+    items: Vec<MenuItem<T>>,
+    selection: usize,
+    open: Option<usize>,
+}
+
+impl<T: Copy + PartialEq> MenuList<T> {
+    /// Creates a menu, the title is drawn above the items if there is one
+    pub fn new(title: Option<&str>, items: Vec<MenuItem<T>>) -> MenuList<T> {
+        MenuList {
+            title: title.map(|title| title.to_string()),
+            items: items,
+            selection: 0,
+            open: None,
+        }
+    }
+
+    /// Selects the first item and closes any open submenu
+    pub fn reset(&mut self) {
+        self.selection = 0;
+        self.open = None;
+    }
+
+    /// Returns the menu currently being shown, which
+    /// is the innermost open submenu if there is one
+    fn active(&self) -> &MenuList<T> {
+        if let Some(i) = self.open {
+            if let MenuItemKind::Submenu(ref menu) = self.items[i].kind {
+                return menu.active();
+            }
+        }
+        self
+    }
+
+    /// Returns the choice under the selection of the menu being shown
+    pub fn selected(&self) -> Option<T> {
+        let menu = self.active();
+        match menu.items.get(menu.selection) {
+            Some(&MenuItem {
+                kind: MenuItemKind::Choice(choice),
+                ..
+            }) => Some(choice),
+            _ => None,
+        }
+    }
+
+    /// Changes the label of every item with this choice, in submenus too
+    pub fn set_label(&mut self, choice: T, label: String) {
+        for item in &mut self.items {
+            match item.kind {
+                MenuItemKind::Choice(c) if c == choice => item.label = label.clone(),
+                MenuItemKind::Submenu(ref mut menu) => menu.set_label(choice, label.clone()),
+                _ => {}
+            }
+        }
+    }
+
+    /// Moves the selection or confirms an item, returning
+    /// anything the game needs to act on
+    pub fn handle(&mut self, action: Action) -> Option<MenuCommand<T>> {
+        if let Some(i) = self.open {
+            if let MenuItemKind::Submenu(ref mut menu) = self.items[i].kind {
+                return match menu.handle(action) {
+                    Some(MenuCommand::Back) => {
+                        self.open = None;
+                        None
+                    }
+                    command => command,
+                };
+            }
+        }
+
+        if self.items.is_empty() {
+            return if action == Action::Pause {
+                Some(MenuCommand::Back)
+            } else {
+                None
+            };
+        }
+
+        let last = self.items.len() - 1;
+        match action {
+            Action::Up if self.selection == 0 => self.selection = last,
+            Action::Up => self.selection -= 1,
+            Action::Down if self.selection == last => self.selection = 0,
+            Action::Down => self.selection += 1,
+            Action::Left | Action::Right => {
+                if let MenuItemKind::Choice(choice) = self.items[self.selection].kind {
+                    return Some(MenuCommand::Adjusted(choice, action == Action::Right));
+                }
+            }
+            Action::Pause => return Some(MenuCommand::Back),
+            Action::Confirm => match self.items[self.selection].kind {
+                MenuItemKind::Choice(choice) => return Some(MenuCommand::Chosen(choice)),
+                MenuItemKind::Submenu(ref mut menu) => {
+                    menu.reset();
+                    self.open = Some(self.selection);
+                }
+                MenuItemKind::Back => return Some(MenuCommand::Back),
+            },
+        }
+        None
+    }
+
+    /// Draws the title in the upper part of the screen with the items centered below it
+    pub fn draw(&self, ctx: &mut Context, assets: &mut Assets) -> GameResult<()> {
+        let menu = self.active();
+        if let Some(ref title) = menu.title {
+            assets.draw_centered(ctx, FontSize::Large, title, SQUARE_SIZE * 3.0)?;
+        }
+
+        // Center the list, but never let a long one run into the title
+        let top: f32 = (WIN_H as f32 / 2.0 - menu.items.len() as f32 * SQUARE_SIZE / 2.0
+            + SQUARE_SIZE)
+            .max(SQUARE_SIZE * 5.0);
+        // Leave the bottom row free for hints
+        let rows = ((WIN_H as f32 - SQUARE_SIZE - top) / SQUARE_SIZE) as usize;
+        menu.draw_items(ctx, assets, top, rows)
+    }
+
+    /// Draws just the items, starting at the given height
+    pub fn draw_at(&self, ctx: &mut Context, assets: &mut Assets, top: f32) -> GameResult<()> {
+        let menu = self.active();
+        menu.draw_items(ctx, assets, top, menu.items.len())
+    }
+
+    /// Draws a centered column of at most the given number of items with
+    /// the small crab sprite pointing at the selected one. Longer lists
+    /// scroll to keep the selected item in view.
+    fn draw_items(
+        &self,
+        ctx: &mut Context,
+        assets: &mut Assets,
+        top: f32,
+        rows: usize,
+    ) -> GameResult<()> {
+        let first = first_visible(self.selection, rows);
+        let mut vertical = top;
+        for (i, item) in self.items.iter().enumerate().skip(first).take(rows) {
+            let horizontal = {
+                let item_text = assets.text(ctx, FontSize::Menu, &item.label)?;
+                let horizontal: f32 = WIN_W as f32 / 2.0 - item_text.width() as f32 / 2.0;
+                let dest_point = graphics::Point2::new(horizontal, vertical);
+                graphics::draw(ctx, item_text, dest_point, 0.0)?;
+                horizontal
+            };
+
+            if i == self.selection {
+                let dest_point = graphics::Point2::new(horizontal - 30.0, vertical + 10.0);
+                graphics::draw(ctx, &assets.small_crab, dest_point, 0.0)?;
+            }
+            vertical += SQUARE_SIZE;
+        }
+        Ok(())
+    }
+}
+
+/// Returns the first item to draw so the selected one is within the rows shown
+fn first_visible(selection: usize, rows: usize) -> usize {
+    if rows == 0 || selection < rows {
+        0
+    } else {
+        selection + 1 - rows
+    }
+}
+
+/// Builds the menu on the title screen
+pub fn main_menu() -> MenuList<MenuChoice> {
+    let start = MenuList::new(
+        None,
+        vec![
+            MenuItem::choice("Arcade", MenuChoice::NewGame),
+            MenuItem::choice("2 Players", MenuChoice::TwoPlayers),
+            MenuItem::choice("Co-op", MenuChoice::CoOp),
+            MenuItem::choice("Versus", MenuChoice::Versus),
+            MenuItem::choice("Practice", MenuChoice::Practice),
+            MenuItem::back("Back"),
+        ],
+    );
+    let online = MenuList::new(
+        None,
+        vec![
+            MenuItem::choice("Host Game", MenuChoice::Host),
+            MenuItem::choice("Join Game", MenuChoice::Join),
+            MenuItem::choice("Spectate", MenuChoice::Spectate),
+            MenuItem::back("Back"),
+        ],
+    );
+    MenuList::new(
+        None,
+        vec![
+            MenuItem::submenu("Start", start),
+            MenuItem::submenu("Online", online),
+            MenuItem::choice("Continue", MenuChoice::Continue),
+            MenuItem::choice("Options", MenuChoice::Options),
+            MenuItem::choice("Scores", MenuChoice::Scores),
+        ],
+    )
+}
+
+/// Builds the menu shown over a paused game
+pub fn pause_menu() -> MenuList<MenuChoice> {
+    MenuList::new(
+        Some("Paused"),
+        vec![
+            MenuItem::choice("Resume", MenuChoice::Resume),
+            MenuItem::choice("Restart", MenuChoice::Restart),
+            MenuItem::choice("Options", MenuChoice::Options),
+            MenuItem::choice("Quit to Main Menu", MenuChoice::QuitToMenu),
+        ],
+    )
+}
+
+/// Builds the options screen, labels are filled in by refresh_options_menu
+pub fn options_menu() -> MenuList<MenuChoice> {
+    let mut items = vec![
+        MenuItem::choice("Music Volume", MenuChoice::MusicVolume),
+        MenuItem::choice("Effects Volume", MenuChoice::EffectsVolume),
+        MenuItem::choice("Broadcast", MenuChoice::Broadcast),
+        MenuItem::choice("Theme", MenuChoice::Theme),
+        MenuItem::choice("Colors", MenuChoice::ColorMode),
+    ];
+    for action in ACTIONS.iter() {
+        items.push(MenuItem::choice(action.label(), MenuChoice::Binding(*action)));
+    }
+    items.push(MenuItem::choice("Reset Keys", MenuChoice::ResetKeys));
+    items.push(MenuItem::back("Back"));
+    MenuList::new(Some("Options"), items)
+}
+
+/// Updates the options screen labels with the current value of each
+/// setting and the keys bound to each action. While rebinding is Some
+/// the action being rebound asks for a key instead of listing its keys.
+pub fn refresh_options_menu(
+    menu: &mut MenuList<MenuChoice>,
+    settings: &Settings,
+    key_map: &KeyMap,
+    rebinding: Option<Action>,
+) {
+    menu.set_label(
+        MenuChoice::MusicVolume,
+        format!("Music Volume: < {} >", (settings.music_volume * 10.0).round()),
+    );
+    menu.set_label(
+        MenuChoice::EffectsVolume,
+        format!("Effects Volume: < {} >", (settings.effects_volume * 10.0).round()),
+    );
+    let broadcast = if settings.broadcast { "On" } else { "Off" };
+    menu.set_label(MenuChoice::Broadcast, format!("Broadcast: < {} >", broadcast));
+    menu.set_label(MenuChoice::Theme, format!("Theme: < {} >", settings.theme));
+    menu.set_label(
+        MenuChoice::ColorMode,
+        format!("Colors: < {} >", settings.color_mode.name()),
+    );
+    for action in ACTIONS.iter() {
+        let label = if rebinding == Some(*action) {
+            format!("{}: press a key...", action.label())
+        } else {
+            let keys: Vec<String> = key_map
+                .keys_for(*action)
+                .iter()
+                .map(|key| key.name())
+                .collect();
+            format!("{}: {}", action.label(), keys.join(", "))
+        };
+        menu.set_label(MenuChoice::Binding(*action), label);
+    }
+}
+
+/// Unit tests for MenuList.
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    fn numbers() -> MenuList<u32> {
+        MenuList::new(
+            None,
+            vec![
+                MenuItem::choice("One", 1),
+                MenuItem::choice("Two", 2),
+                MenuItem::back("Back"),
+            ],
+        )
+    }
+
+    #[test]
+    fn selection_wraps_around() {
+        let mut menu = numbers();
+
+        menu.handle(Action::Up);
+        assert_eq!(menu.selected(), None);
+        menu.handle(Action::Down);
+        assert_eq!(menu.selected(), Some(1));
+    }
+
+    #[test]
+    fn confirm_and_adjust_hand_back_the_choice() {
+        let mut menu = numbers();
+        menu.handle(Action::Down);
+
+        assert_eq!(menu.handle(Action::Confirm), Some(MenuCommand::Chosen(2)));
+        assert_eq!(menu.handle(Action::Left), Some(MenuCommand::Adjusted(2, false)));
+        assert_eq!(menu.handle(Action::Pause), Some(MenuCommand::Back));
+    }
+
+    #[test]
+    fn submenu_opens_and_backs_out() {
+        let mut menu = MenuList::new(
+            None,
+            vec![
+                MenuItem::submenu("Numbers", numbers()),
+                MenuItem::choice("Three", 3),
+            ],
+        );
+
+        assert_eq!(menu.handle(Action::Confirm), None);
+        assert_eq!(menu.selected(), Some(1));
+
+        // Backing out of the submenu stays in the menu
+        assert_eq!(menu.handle(Action::Pause), None);
+        assert_eq!(menu.selected(), None);
+        assert_eq!(menu.handle(Action::Pause), Some(MenuCommand::Back));
+    }
+
+    #[test]
+    fn set_label_reaches_submenus() {
+        let mut menu = MenuList::new(None, vec![MenuItem::submenu("Numbers", numbers())]);
+        menu.set_label(2, "Deux".to_string());
+
+        menu.handle(Action::Confirm);
+        assert_eq!(menu.active().items[1].label, "Deux");
+    }
+
+    #[test]
+    fn long_lists_scroll_to_the_selection() {
+        assert_eq!(first_visible(3, 10), 0);
+        assert_eq!(first_visible(12, 10), 3);
+    }
+}
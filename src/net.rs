@@ -0,0 +1,411 @@
+/*
+Copyright (c) 2018 Matt Carnovale, Julie Rutherford-Fields, Joshua Sander
+This work is available under the "MIT License”.
+Please see the file LICENSE in this distribution
+for license terms.
+*/
+
+use constants::{NET_CONNECT_TIMEOUT_MS, NET_INPUT_DELAY, NET_MAX_LINE_BYTES};
+use controls::Action;
+use rand::{thread_rng, Rng};
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use serde_json;
+use std::collections::HashMap;
+use std::io::{self, ErrorKind, Read, Write};
+use std::mem;
+use std::net::{TcpListener, TcpStream, ToSocketAddrs};
+use std::time::Duration;
+
+/// Which end of the connection this game is. The host's
+/// crab is player one and the guest's is player two.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Role {
+    Host,
+    Guest,
+}
+
+/// Everything sent between the two games, one JSON object per line
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+enum NetMessage {
+    /// Sent by the host as soon as the guest connects
+    Hello { seed: u64 },
+    /// The actions one player took during a frame
+    Input { frame: u64, actions: Vec<Action> },
+}
+
+/// The actions both players took during one frame of the game
+#[derive(Clone, Debug, PartialEq)]
+pub struct FrameInputs {
+    pub host: Vec<Action>,
+    pub guest: Vec<Action>,
+}
+
+/// A connection to the other game, kept in lockstep. Both games build the
+/// board from the same seed and only step it once they have the actions
+/// both players took that frame, so the board stays the same on each side.
+/// Local actions are scheduled a few frames ahead to hide the round trip.
+pub struct NetSession {
+    lines: JsonLines,
+    role: Role,
+    seed: Option<u64>,
+    frame: u64,
+    next_send: u64,
+    local: HashMap<u64, Vec<Action>>,
+    remote: HashMap<u64, Vec<Action>>,
+}
+
+/// Starts listening for a guest on every interface
+pub fn listen(port: u16) -> io::Result<TcpListener> {
+    let listener = TcpListener::bind(("0.0.0.0", port))?;
+    listener.set_nonblocking(true)?;
+    Ok(listener)
+}
+
+/// Checks for a guest without waiting. Once one connects the host
+/// picks the seed and sends it over, and the session is ready to play.
+pub fn accept(listener: &TcpListener) -> io::Result<Option<NetSession>> {
+    match listener.accept() {
+        Ok((stream, _)) => {
+            let mut session = NetSession::new(stream, Role::Host)?;
+            let seed = thread_rng().gen();
+            session.seed = Some(seed);
+            session.lines.send(&NetMessage::Hello { seed })?;
+            Ok(Some(session))
+        }
+        Err(ref e) if e.kind() == ErrorKind::WouldBlock => Ok(None),
+        Err(e) => Err(e),
+    }
+}
+
+/// Connects to a host, giving up after a few seconds.
+/// The session is ready to play once seed() returns Some.
+pub fn connect(address: &str) -> io::Result<NetSession> {
+    let address = address
+        .to_socket_addrs()?
+        .next()
+        .ok_or_else(|| io::Error::new(ErrorKind::InvalidInput, "no address to connect to"))?;
+    let timeout = Duration::from_millis(NET_CONNECT_TIMEOUT_MS);
+    let stream = TcpStream::connect_timeout(&address, timeout)?;
+    NetSession::new(stream, Role::Guest)
+}
+
+impl NetSession {
+    /// Wraps a connected stream. Nobody acts during the first few
+    /// frames, which gives the first real actions time to arrive.
+    fn new(stream: TcpStream, role: Role) -> io::Result<NetSession> {
+        let mut local = HashMap::new();
+        let mut remote = HashMap::new();
+        for frame in 0..NET_INPUT_DELAY {
+            local.insert(frame, vec![]);
+            remote.insert(frame, vec![]);
+        }
+        Ok(NetSession {
+            lines: JsonLines::new(stream, NET_MAX_LINE_BYTES)?,
+            role: role,
+            seed: None,
+            frame: 0,
+            next_send: NET_INPUT_DELAY,
+            local: local,
+            remote: remote,
+        })
+    }
+
+    /// Returns which end of the connection this game is
+    pub fn role(&self) -> Role {
+        self.role
+    }
+
+    /// Returns the seed both games build the board from, once the host has sent it
+    pub fn seed(&self) -> Option<u64> {
+        self.seed
+    }
+
+    /// Sends the local player's actions, unless this game is already as
+    /// far ahead of the current frame as it is allowed to be. Actions
+    /// that can't be sent yet are left for the next call.
+    pub fn send_actions(&mut self, actions: &mut Vec<Action>) -> io::Result<()> {
+        if self.next_send <= self.frame + NET_INPUT_DELAY {
+            let frame = self.next_send;
+            let actions = mem::replace(actions, vec![]);
+            self.local.insert(frame, actions.clone());
+            self.lines.send(&NetMessage::Input { frame, actions })?;
+            self.next_send += 1;
+        }
+        self.lines.flush()
+    }
+
+    /// Reads whatever the other game has sent without waiting for more.
+    /// Fails if the other game sends actions for a frame it can't have
+    /// reached: one already played, or further ahead than it is allowed
+    /// to get while waiting on this game's actions.
+    pub fn receive(&mut self) -> io::Result<()> {
+        for message in self.lines.receive()? {
+            match message {
+                NetMessage::Hello { seed } => self.seed = Some(seed),
+                NetMessage::Input { frame, actions } => {
+                    if frame < self.frame || frame > self.frame + 2 * NET_INPUT_DELAY + 1 {
+                        return Err(io::Error::new(
+                            ErrorKind::InvalidData,
+                            "actions sent for a frame out of range",
+                        ));
+                    }
+                    self.remote.insert(frame, actions);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Returns both players' actions for the next frame and moves on to the
+    /// one after, or None if the other game's actions haven't arrived yet
+    pub fn advance(&mut self) -> Option<FrameInputs> {
+        if !self.local.contains_key(&self.frame) || !self.remote.contains_key(&self.frame) {
+            return None;
+        }
+        let local = self.local.remove(&self.frame).unwrap_or_default();
+        let remote = self.remote.remove(&self.frame).unwrap_or_default();
+        self.frame += 1;
+        Some(match self.role {
+            Role::Host => FrameInputs {
+                host: local,
+                guest: remote,
+            },
+            Role::Guest => FrameInputs {
+                host: remote,
+                guest: local,
+            },
+        })
+    }
+}
+
+/// A socket carrying one JSON message per line, which
+/// is read from and written to without ever waiting
+pub struct JsonLines {
+    stream: TcpStream,
+    incoming: Vec<u8>,
+    outgoing: Vec<u8>,
+    max_line: usize,
+}
+
+impl JsonLines {
+    /// Wraps a connected stream that won't accept a line longer than max_line bytes
+    pub fn new(stream: TcpStream, max_line: usize) -> io::Result<JsonLines> {
+        stream.set_nonblocking(true)?;
+        stream.set_nodelay(true)?;
+        Ok(JsonLines {
+            stream: stream,
+            incoming: vec![],
+            outgoing: vec![],
+            max_line: max_line,
+        })
+    }
+
+    /// Returns true once everything sent has been handed to the socket
+    pub fn is_flushed(&self) -> bool {
+        self.outgoing.is_empty()
+    }
+
+    /// Queues a message and writes as much as the socket will take
+    pub fn send<T: Serialize>(&mut self, message: &T) -> io::Result<()> {
+        let json =
+            serde_json::to_vec(message).map_err(|e| io::Error::new(ErrorKind::InvalidData, e))?;
+        self.outgoing.extend_from_slice(&json);
+        self.outgoing.push(b'\n');
+        self.flush()
+    }
+
+    /// Returns every complete message that has arrived so far, oldest first.
+    /// Fails once a line gets longer than this connection accepts.
+    pub fn receive<T: DeserializeOwned>(&mut self) -> io::Result<Vec<T>> {
+        let mut buffer = [0; 1024];
+        let mut messages = vec![];
+        loop {
+            match self.stream.read(&mut buffer) {
+                Ok(0) => {
+                    return Err(io::Error::new(
+                        ErrorKind::ConnectionAborted,
+                        "the connection was closed",
+                    ))
+                }
+                Ok(n) => self.incoming.extend_from_slice(&buffer[..n]),
+                Err(ref e) if e.kind() == ErrorKind::WouldBlock => break,
+                Err(ref e) if e.kind() == ErrorKind::Interrupted => continue,
+                Err(e) => return Err(e),
+            }
+            self.take_lines(&mut messages)?;
+        }
+        Ok(messages)
+    }
+
+    /// Moves every complete line read so far into messages
+    fn take_lines<T: DeserializeOwned>(&mut self, messages: &mut Vec<T>) -> io::Result<()> {
+        while let Some(end) = self.incoming.iter().position(|byte| *byte == b'\n') {
+            if end > self.max_line {
+                break;
+            }
+            let line: Vec<u8> = self.incoming.drain(..end + 1).collect();
+            let message = serde_json::from_slice(&line[..end])
+                .map_err(|e| io::Error::new(ErrorKind::InvalidData, e))?;
+            messages.push(message);
+        }
+        if self.incoming.len() > self.max_line {
+            return Err(io::Error::new(ErrorKind::InvalidData, "message too long"));
+        }
+        Ok(())
+    }
+
+    /// Writes as much of the queued output as the socket will take without waiting
+    pub fn flush(&mut self) -> io::Result<()> {
+        while !self.outgoing.is_empty() {
+            match self.stream.write(&self.outgoing) {
+                Ok(0) => return Err(io::Error::new(ErrorKind::WriteZero, "connection closed")),
+                Ok(n) => {
+                    self.outgoing.drain(..n);
+                }
+                Err(ref e) if e.kind() == ErrorKind::WouldBlock => break,
+                Err(ref e) if e.kind() == ErrorKind::Interrupted => continue,
+                Err(e) => return Err(e),
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Unit tests for NetSession, run over a real connection on localhost.
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use std::thread;
+
+    /// Connects a host and a guest and waits for the seed to arrive
+    fn connected_pair() -> (NetSession, NetSession) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap().to_string();
+        listener.set_nonblocking(true).unwrap();
+
+        let mut guest = connect(&address).unwrap();
+        let mut host = None;
+        while host.is_none() {
+            host = accept(&listener).unwrap();
+        }
+        while guest.seed().is_none() {
+            guest.receive().unwrap();
+            thread::sleep(Duration::from_millis(1));
+        }
+        (host.unwrap(), guest)
+    }
+
+    /// Sends, receives and advances until a frame comes out
+    fn step(session: &mut NetSession, actions: &mut Vec<Action>) -> FrameInputs {
+        loop {
+            session.send_actions(actions).unwrap();
+            session.receive().unwrap();
+            if let Some(inputs) = session.advance() {
+                return inputs;
+            }
+            thread::sleep(Duration::from_millis(1));
+        }
+    }
+
+    #[test]
+    fn guest_gets_the_hosts_seed() {
+        let (host, guest) = connected_pair();
+
+        assert_eq!(host.role(), Role::Host);
+        assert_eq!(guest.role(), Role::Guest);
+        assert_eq!(host.seed(), guest.seed());
+    }
+
+    #[test]
+    fn both_sides_see_the_same_inputs() {
+        let (mut host, mut guest) = connected_pair();
+
+        // The first frames are empty while actions are in flight
+        for _ in 0..NET_INPUT_DELAY {
+            let empty = FrameInputs {
+                host: vec![],
+                guest: vec![],
+            };
+            assert_eq!(step(&mut host, &mut vec![Action::Up]), empty);
+            assert_eq!(step(&mut guest, &mut vec![Action::Left]), empty);
+        }
+
+        let expected = FrameInputs {
+            host: vec![Action::Up],
+            guest: vec![Action::Left],
+        };
+        assert_eq!(step(&mut host, &mut vec![]), expected);
+        assert_eq!(step(&mut guest, &mut vec![]), expected);
+    }
+
+    #[test]
+    fn no_frame_without_the_other_players_actions() {
+        let (mut host, _guest) = connected_pair();
+
+        for _ in 0..NET_INPUT_DELAY {
+            host.advance().unwrap();
+        }
+        host.send_actions(&mut vec![]).unwrap();
+        host.receive().unwrap();
+        assert_eq!(host.advance(), None);
+    }
+
+    #[test]
+    fn guest_running_ahead_of_a_stalled_host_is_not_refused() {
+        let (mut host, mut guest) = connected_pair();
+        host.send_actions(&mut vec![]).unwrap();
+
+        // The guest plays every frame it has the host's actions for
+        for _ in 0..NET_INPUT_DELAY + 1 {
+            step(&mut guest, &mut vec![]);
+        }
+        guest.send_actions(&mut vec![]).unwrap();
+        thread::sleep(Duration::from_millis(50));
+
+        host.receive().unwrap();
+        assert!(host.remote.contains_key(&(2 * NET_INPUT_DELAY + 1)));
+    }
+
+    /// Connects a host to a bare socket standing in for a misbehaving guest
+    fn host_and_raw_guest() -> (NetSession, TcpStream) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let guest = TcpStream::connect(listener.local_addr().unwrap()).unwrap();
+        listener.set_nonblocking(true).unwrap();
+        let mut host = None;
+        while host.is_none() {
+            host = accept(&listener).unwrap();
+        }
+        (host.unwrap(), guest)
+    }
+
+    /// Receives until the host gives up on the connection
+    fn receive_until_error(host: &mut NetSession) -> io::Error {
+        loop {
+            if let Err(e) = host.receive() {
+                return e;
+            }
+            thread::sleep(Duration::from_millis(1));
+        }
+    }
+
+    #[test]
+    fn actions_far_in_the_future_are_refused() {
+        let (mut host, mut guest) = host_and_raw_guest();
+        guest
+            .write_all(b"{\"Input\":{\"frame\":1000,\"actions\":[]}}\n")
+            .unwrap();
+
+        assert_eq!(receive_until_error(&mut host).kind(), ErrorKind::InvalidData);
+        assert!(!host.remote.contains_key(&1000));
+    }
+
+    #[test]
+    fn line_without_an_end_is_refused() {
+        let (mut host, mut guest) = host_and_raw_guest();
+        guest.write_all(&vec![b' '; NET_MAX_LINE_BYTES * 2]).unwrap();
+
+        assert_eq!(receive_until_error(&mut host).kind(), ErrorKind::InvalidData);
+    }
+}
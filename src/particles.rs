@@ -0,0 +1,363 @@
+/*
+Copyright (c) 2018 Matt Carnovale, Julie Rutherford-Fields, Joshua Sander
+This work is available under the "MIT License”.
+Please see the file LICENSE in this distribution
+for license terms.
+*/
+
+use constants::MAX_PARTICLES;
+use ggez::graphics::spritebatch::SpriteBatch;
+use ggez::graphics::{self, Color, DrawParam, Image, Point2};
+use ggez::{Context, GameResult};
+use rand::Rng;
+use rng::GameRng;
+
+/// How the particles from an emitter look and move
+#[derive(Clone, Debug)]
+pub struct ParticleStyle {
+    /// Fastest a particle starts moving in any direction, in pixels per frame
+    pub spread: f32,
+    /// The way every particle heads on top of its random start
+    pub drift: Point2,
+    /// Added to each particle's downward speed every frame
+    pub gravity: f32,
+    /// Frames each particle lasts
+    pub lifetime: u32,
+    /// Width and height of each particle in pixels
+    pub size: f32,
+    /// Each particle starts as one of these, picked at random
+    pub colors: Vec<Color>,
+    /// The color particles fade into by the end of their lifetime
+    pub fade_to: Color,
+}
+
+/// Releases particles from one spot for a number of frames
+#[derive(Clone, Debug)]
+pub struct Emitter {
+    pub x: f32,
+    pub y: f32,
+    /// Particles released each frame. A fraction is released on average,
+    /// so 0.25 is one particle every four frames or so.
+    pub rate: f32,
+    /// Frames left to release particles for
+    pub lifetime: u32,
+    pub style: ParticleStyle,
+}
+
+/// One particle in flight
+struct Particle {
+    position: Point2,
+    velocity: Point2,
+    gravity: f32,
+    age: u32,
+    lifetime: u32,
+    size: f32,
+    color: Color,
+    fade_to: Color,
+}
+
+impl Particle {
+    /// Returns the particle's color partway through fading
+    fn current_color(&self) -> Color {
+        let t = self.age as f32 / self.lifetime as f32;
+        Color::new(
+            self.color.r + (self.fade_to.r - self.color.r) * t,
+            self.color.g + (self.fade_to.g - self.color.g) * t,
+            self.color.b + (self.fade_to.b - self.color.b) * t,
+            self.color.a + (self.fade_to.a - self.color.a) * t,
+        )
+    }
+}
+
+/// A burst of water thrown up where the crab went under
+pub fn splash(x: f32, y: f32) -> Emitter {
+    Emitter {
+        x: x,
+        y: y,
+        rate: 24.0,
+        lifetime: 1,
+        style: ParticleStyle {
+            spread: 3.0,
+            drift: Point2::new(0.0, -1.5),
+            gravity: 0.15,
+            lifetime: 30,
+            size: 4.0,
+            colors: vec![
+                Color::new(0.8, 0.9, 1.0, 1.0),
+                Color::new(0.5, 0.7, 1.0, 1.0),
+            ],
+            fade_to: Color::new(0.3, 0.5, 1.0, 0.0),
+        },
+    }
+}
+
+/// Foam left on the water behind a log or turtle.
+/// Going right means the foam drifts off to the left.
+fn wake_style(going_right: bool) -> ParticleStyle {
+    let behind = if going_right { -0.3 } else { 0.3 };
+    ParticleStyle {
+        spread: 0.3,
+        drift: Point2::new(behind, 0.0),
+        gravity: 0.0,
+        lifetime: 40,
+        size: 3.0,
+        colors: vec![Color::new(0.9, 0.95, 1.0, 0.8)],
+        fade_to: Color::new(0.9, 0.95, 1.0, 0.0),
+    }
+}
+
+/// Bits of colored paper thrown up over a cubby that was just filled
+pub fn confetti(x: f32, y: f32) -> Emitter {
+    Emitter {
+        x: x,
+        y: y,
+        rate: 20.0,
+        lifetime: 3,
+        style: ParticleStyle {
+            spread: 4.0,
+            drift: Point2::new(0.0, -2.0),
+            gravity: 0.12,
+            lifetime: 50,
+            size: 5.0,
+            colors: vec![
+                Color::new(1.0, 0.2, 0.2, 1.0),
+                Color::new(1.0, 0.9, 0.2, 1.0),
+                Color::new(0.2, 1.0, 0.3, 1.0),
+                Color::new(0.3, 0.6, 1.0, 1.0),
+                Color::new(1.0, 0.4, 1.0, 1.0),
+            ],
+            fade_to: Color::new(1.0, 1.0, 1.0, 0.0),
+        },
+    }
+}
+
+/// Smoke puffed out of the back of a truck.
+/// Going right means the smoke drifts off to the left.
+fn exhaust_style(going_right: bool) -> ParticleStyle {
+    let behind = if going_right { -0.6 } else { 0.6 };
+    ParticleStyle {
+        spread: 0.4,
+        drift: Point2::new(behind, -0.3),
+        gravity: 0.0,
+        lifetime: 35,
+        size: 6.0,
+        colors: vec![Color::new(0.4, 0.4, 0.4, 0.7)],
+        fade_to: Color::new(0.7, 0.7, 0.7, 0.0),
+    }
+}
+
+/// Releases rate particles in the given style from one spot. A fraction
+/// is released on average, so 0.25 is one particle every four calls or so.
+fn release(
+    particles: &mut Vec<Particle>,
+    rng: &mut GameRng,
+    x: f32,
+    y: f32,
+    rate: f32,
+    style: &ParticleStyle,
+) {
+    let mut count = rate.floor() as usize;
+    if rng.gen::<f32>() < rate.fract() {
+        count += 1;
+    }
+    for _ in 0..count {
+        // Keep busy screens from piling up particles forever
+        if particles.len() >= MAX_PARTICLES {
+            break;
+        }
+        let color = *rng.choose(&style.colors).unwrap_or(&graphics::WHITE);
+        let spread_x = (rng.gen::<f32>() * 2.0 - 1.0) * style.spread;
+        let spread_y = (rng.gen::<f32>() * 2.0 - 1.0) * style.spread;
+        particles.push(Particle {
+            position: Point2::new(x, y),
+            velocity: Point2::new(style.drift.x + spread_x, style.drift.y + spread_y),
+            gravity: style.gravity,
+            age: 0,
+            lifetime: style.lifetime.max(1),
+            size: style.size,
+            color: color,
+            fade_to: style.fade_to,
+        });
+    }
+}
+
+/// Every emitter and particle on screen. Particles are only ever drawn,
+/// so they use their own random numbers and never touch the board's.
+pub struct ParticleSystem {
+    emitters: Vec<Emitter>,
+    particles: Vec<Particle>,
+    rng: GameRng,
+    batch: Option<SpriteBatch>,
+    /// Wake and exhaust styles going left then right, built once
+    /// since trails are released from every object every frame
+    wakes: [ParticleStyle; 2],
+    exhausts: [ParticleStyle; 2],
+}
+
+impl ParticleSystem {
+    /// Creates a system with nothing in it
+    pub fn new() -> ParticleSystem {
+        ParticleSystem {
+            emitters: vec![],
+            particles: vec![],
+            rng: GameRng::from_entropy(),
+            batch: None,
+            wakes: [wake_style(false), wake_style(true)],
+            exhausts: [exhaust_style(false), exhaust_style(true)],
+        }
+    }
+
+    /// Adds an emitter, which starts releasing particles on the next update
+    pub fn add(&mut self, emitter: Emitter) {
+        self.emitters.push(emitter);
+    }
+
+    /// Leaves foam behind a log or turtle whose back end is at x
+    pub fn wake(&mut self, x: f32, y: f32, going_right: bool) {
+        let style = &self.wakes[going_right as usize];
+        release(&mut self.particles, &mut self.rng, x, y, 0.2, style);
+    }
+
+    /// Puffs smoke out of a truck whose back end is at x
+    pub fn exhaust(&mut self, x: f32, y: f32, going_right: bool) {
+        let style = &self.exhausts[going_right as usize];
+        release(&mut self.particles, &mut self.rng, x, y, 0.15, style);
+    }
+
+    /// Returns how many particles are in flight
+    pub fn len(&self) -> usize {
+        self.particles.len()
+    }
+
+    /// Returns true if there are no particles in flight
+    pub fn is_empty(&self) -> bool {
+        self.particles.is_empty()
+    }
+
+    /// Removes every emitter and particle, for when the board is reset
+    pub fn clear(&mut self) {
+        self.emitters.clear();
+        self.particles.clear();
+    }
+
+    /// Releases new particles, moves every particle on by a frame
+    /// and removes the ones that have faded away
+    pub fn update(&mut self) {
+        for emitter in &mut self.emitters {
+            release(
+                &mut self.particles,
+                &mut self.rng,
+                emitter.x,
+                emitter.y,
+                emitter.rate,
+                &emitter.style,
+            );
+            emitter.lifetime = emitter.lifetime.saturating_sub(1);
+        }
+        self.emitters.retain(|emitter| emitter.lifetime > 0);
+
+        for particle in &mut self.particles {
+            particle.position.x += particle.velocity.x;
+            particle.position.y += particle.velocity.y;
+            particle.velocity.y += particle.gravity;
+            particle.age += 1;
+        }
+        self.particles.retain(|particle| particle.age < particle.lifetime);
+    }
+
+    /// Draws every particle as a square of the given image, normally a
+    /// single white pixel, all in one draw call
+    pub fn draw(&mut self, ctx: &mut Context, pixel: &Image) -> GameResult<()> {
+        if self.particles.is_empty() {
+            return Ok(());
+        }
+        let batch = self.batch
+            .get_or_insert_with(|| SpriteBatch::new(pixel.clone()));
+        for particle in &self.particles {
+            batch.add(DrawParam {
+                dest: Point2::new(
+                    particle.position.x - particle.size / 2.0,
+                    particle.position.y - particle.size / 2.0,
+                ),
+                scale: Point2::new(particle.size, particle.size),
+                color: Some(particle.current_color()),
+                ..Default::default()
+            });
+        }
+        graphics::draw_ex(
+            ctx,
+            batch,
+            DrawParam {
+                color: Some(graphics::WHITE),
+                ..Default::default()
+            },
+        )?;
+        batch.clear();
+        Ok(())
+    }
+}
+
+impl Default for ParticleSystem {
+    fn default() -> ParticleSystem {
+        ParticleSystem::new()
+    }
+}
+
+/// Unit tests for ParticleSystem.
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    #[test]
+    fn burst_releases_its_particles_then_they_fade_away() {
+        let mut particles = ParticleSystem::new();
+        particles.add(splash(100.0, 100.0));
+
+        particles.update();
+        assert_eq!(particles.len(), 24);
+
+        for _ in 0..30 {
+            particles.update();
+        }
+        assert!(particles.is_empty());
+    }
+
+    #[test]
+    fn particles_fade_towards_their_end_color() {
+        let mut particles = ParticleSystem::new();
+        particles.add(confetti(100.0, 100.0));
+        particles.update();
+
+        let start = particles.particles[0].current_color().a;
+        for _ in 0..25 {
+            particles.update();
+        }
+        assert!(particles.particles[0].current_color().a < start);
+    }
+
+    #[test]
+    fn trails_release_particles_without_emitters() {
+        let mut particles = ParticleSystem::new();
+        for _ in 0..100 {
+            particles.wake(100.0, 100.0, true);
+            particles.exhaust(100.0, 100.0, false);
+        }
+
+        assert!(!particles.is_empty());
+        assert!(particles.emitters.is_empty());
+        assert!(particles.particles.iter().all(|particle| particle.age == 0));
+    }
+
+    #[test]
+    fn particles_stop_at_the_limit() {
+        let mut particles = ParticleSystem::new();
+        for _ in 0..MAX_PARTICLES {
+            particles.add(splash(0.0, 0.0));
+        }
+
+        particles.update();
+
+        assert_eq!(particles.len(), MAX_PARTICLES);
+    }
+}
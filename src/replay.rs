@@ -0,0 +1,227 @@
+/*
+Copyright (c) 2018 Matt Carnovale, Julie Rutherford-Fields, Joshua Sander
+This work is available under the "MIT License”.
+Please see the file LICENSE in this distribution
+for license terms.
+*/
+
+use background::Cubbies;
+use characters::Crab;
+use collisions;
+use constants::{LANE_MODIFIER, MAX_REPLAY_FRAMES, NUM_LANE, NUM_LOG,
+                RIVER_LANE_MODIFIER, START, WINNING_CUBBIES, WIN_W};
+use controls::Action;
+use leaderboard::ScoreEntry;
+use river::RiverLane;
+use rng::GameRng;
+use traffic::Lane;
+
+/// One hop the player made, and the frame it was made before
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct ReplayInput {
+    pub frame: u64,
+    pub action: Action,
+}
+
+/// Every hop made in an arcade game, enough to play the whole game
+/// again from its seed and check the score it ended with
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct Replay {
+    pub inputs: Vec<ReplayInput>,
+    /// Frames played so far, including the one being played now
+    pub frames: u64,
+}
+
+impl Replay {
+    /// Creates an empty replay for a new game
+    pub fn new() -> Replay {
+        Replay::default()
+    }
+
+    /// Records a hop made before the next frame
+    pub fn record(&mut self, action: Action) {
+        self.inputs.push(ReplayInput {
+            frame: self.frames,
+            action: action,
+        });
+    }
+
+    /// Moves on to the next frame
+    pub fn advance(&mut self) {
+        self.frames += 1;
+    }
+}
+
+/// The rules of an arcade game with nothing drawn, so a
+/// replay can be played back without a window
+pub struct Simulation {
+    crab: Crab,
+    lanes: Vec<Lane>,
+    lane_modifier: f32,
+    river_lanes: Vec<RiverLane>,
+    river_lane_modifier: f32,
+    cubbies: Cubbies,
+    rng: GameRng,
+    level: u32,
+}
+
+impl Simulation {
+    /// Sets up the board a game with this seed starts on
+    pub fn new(seed: u64) -> Simulation {
+        Simulation {
+            crab: Crab::new(WIN_W, START as u32),
+            lanes: vec![],
+            lane_modifier: LANE_MODIFIER,
+            river_lanes: vec![],
+            river_lane_modifier: RIVER_LANE_MODIFIER,
+            cubbies: Cubbies::construct(),
+            rng: GameRng::new(seed),
+            level: 1,
+        }
+    }
+
+    /// Returns the level being played, starting from 1
+    pub fn level(&self) -> u32 {
+        self.level
+    }
+
+    /// Hops the crab, unless it is out of lives
+    pub fn hop(&mut self, action: Action) {
+        if self.crab.get_lives() > 0 {
+            self.crab.hop(action);
+        }
+    }
+
+    /// Plays one frame the same way the game does. Returns the final
+    /// score if the game ended on this frame. The game starts its next
+    /// game from a new seed and replay, so playing back stops here.
+    pub fn step(&mut self) -> Option<isize> {
+        if (self.lanes.len() as u32) < NUM_LANE {
+            self.lanes
+                .push(Lane::construct(self.lane_modifier, &mut self.rng));
+            self.lane_modifier += 1.0;
+        }
+
+        if self.crab.get_lives() > 0 {
+            collisions::check_collisions(&mut self.crab, &mut self.lanes, &mut self.river_lanes);
+            collisions::enter_cubbie(&mut self.crab, &mut self.cubbies);
+        }
+        // Nothing is listening for events here
+        self.crab.take_events();
+
+        for lane in &mut self.lanes {
+            lane.update_vehicles_in_lane();
+        }
+
+        if (self.river_lanes.len() as u32) < NUM_LOG {
+            self.river_lanes
+                .push(RiverLane::construct(self.river_lane_modifier, &mut self.rng));
+            self.river_lane_modifier += 1.0;
+        }
+
+        for river_lane in &mut self.river_lanes {
+            river_lane.update_river_transports_in_river_lane();
+        }
+
+        // The game waits for the last death to play out before ending,
+        // but plays no frames meanwhile, so the score is the same here
+        let mut final_score = None;
+        let out_of_lives = self.crab.get_lives() <= 0;
+        if out_of_lives || self.cubbies.get_filled_cubbies() == WINNING_CUBBIES {
+            let victory = self.cubbies.get_filled_cubbies() == WINNING_CUBBIES;
+            self.cubbies = Cubbies::construct();
+            if victory {
+                self.level += 1;
+            } else {
+                final_score = Some(self.crab.get_score());
+                self.crab.set_lives();
+                self.crab.reset_score();
+            }
+        }
+
+        if self.crab.get_life_lost() {
+            self.crab.respawn();
+        }
+
+        final_score
+    }
+}
+
+/// Plays a replay back from its seed. Returns the score and level
+/// the game ended with, or None if it didn't end on its last frame.
+pub fn play_back(seed: u64, replay: &Replay) -> Option<(isize, u32)> {
+    // Don't let a made up replay keep whoever checks it busy forever
+    if replay.frames > MAX_REPLAY_FRAMES {
+        return None;
+    }
+
+    let mut simulation = Simulation::new(seed);
+    let mut inputs = replay.inputs.iter().peekable();
+    let mut result = None;
+    for frame in 0..replay.frames {
+        while let Some(input) = inputs.peek().filter(|input| input.frame == frame).cloned() {
+            simulation.hop(input.action);
+            inputs.next();
+        }
+        let level = simulation.level();
+        result = simulation.step().map(|score| (score, level));
+    }
+    result
+}
+
+/// Returns true if playing the entry's replay back ends with the same score and level it claims
+pub fn verify(entry: &ScoreEntry) -> bool {
+    play_back(entry.seed, &entry.replay) == Some((entry.score, entry.level))
+}
+
+/// Unit tests for Replay and Simulation.
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    /// Plays a game that hops straight up every few frames until it ends
+    fn hop_until_game_over(seed: u64) -> (Replay, isize) {
+        let mut simulation = Simulation::new(seed);
+        let mut replay = Replay::new();
+        loop {
+            if replay.frames % 10 == 0 {
+                replay.record(Action::Up);
+                simulation.hop(Action::Up);
+            }
+            replay.advance();
+            if let Some(score) = simulation.step() {
+                return (replay, score);
+            }
+        }
+    }
+
+    #[test]
+    fn replay_gives_back_the_same_score() {
+        let (replay, score) = hop_until_game_over(7);
+
+        assert_eq!(play_back(7, &replay), Some((score, 1)));
+    }
+
+    #[test]
+    fn changed_score_or_seed_is_rejected() {
+        let (replay, score) = hop_until_game_over(7);
+        let mut entry = ScoreEntry {
+            name: "CRAB".to_string(),
+            score: score,
+            seed: 7,
+            level: 1,
+            replay: replay,
+        };
+        assert!(verify(&entry));
+
+        entry.score += 500;
+        assert!(!verify(&entry));
+
+        entry.score -= 500;
+        entry.seed = 8;
+        entry.replay.inputs.clear();
+        assert!(!verify(&entry));
+    }
+
+}
@@ -0,0 +1,122 @@
+/*
+Copyright (c) 2018 Matt Carnovale, Julie Rutherford-Fields, Joshua Sander
+This work is available under the "MIT License”.
+Please see the file LICENSE in this distribution
+for license terms.
+*/
+
+use characters::CrabState;
+use std::collections::VecDeque;
+
+/// The state of the board for a single frame: the crab, which
+/// cubbies are filled and the x coordinate of every object in every lane
+pub struct Snapshot {
+    pub crab: CrabState,
+    pub cubbies: Vec<bool>,
+    pub lanes: Vec<Vec<f32>>,
+    pub river_lanes: Vec<Vec<f32>>,
+}
+
+/// A ring buffer holding the most recent snapshots of the board.
+/// Once it is full the oldest snapshot is dropped for each new one.
+pub struct RewindBuffer {
+    snapshots: VecDeque<Snapshot>,
+    capacity: usize,
+}
+
+impl RewindBuffer {
+    /// Creates an empty buffer holding at most capacity snapshots
+    pub fn new(capacity: usize) -> RewindBuffer {
+        RewindBuffer {
+            snapshots: VecDeque::with_capacity(capacity),
+            capacity,
+        }
+    }
+
+    /// Records a snapshot, forgetting the oldest one if the buffer is full
+    pub fn push(&mut self, snapshot: Snapshot) {
+        if self.snapshots.len() == self.capacity {
+            self.snapshots.pop_front();
+        }
+        self.snapshots.push_back(snapshot);
+    }
+
+    /// Takes the most recent snapshot, stepping one frame back in time
+    pub fn pop(&mut self) -> Option<Snapshot> {
+        self.snapshots.pop_back()
+    }
+
+    /// Forgets every snapshot
+    pub fn clear(&mut self) {
+        self.snapshots.clear();
+    }
+
+    /// Returns the number of frames that can currently be rewound
+    pub fn len(&self) -> usize {
+        self.snapshots.len()
+    }
+
+    /// Returns true if there is nothing left to rewind
+    pub fn is_empty(&self) -> bool {
+        self.snapshots.is_empty()
+    }
+}
+
+/// Unit tests for RewindBuffer.
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use characters::DeathCounts;
+
+    fn snapshot(x: f32) -> Snapshot {
+        Snapshot {
+            crab: CrabState {
+                x,
+                y: 0.0,
+                lives: 3,
+                score: 0,
+                speed: 0.0,
+                direction: false,
+                progress: 0.0,
+                deaths: DeathCounts::default(),
+            },
+            cubbies: vec![],
+            lanes: vec![vec![x]],
+            river_lanes: vec![],
+        }
+    }
+
+    #[test]
+    fn pop_returns_most_recent_first() {
+        let mut buffer = RewindBuffer::new(10);
+        buffer.push(snapshot(1.0));
+        buffer.push(snapshot(2.0));
+
+        assert_eq!(buffer.pop().unwrap().crab.x, 2.0);
+        assert_eq!(buffer.pop().unwrap().crab.x, 1.0);
+        assert!(buffer.pop().is_none());
+    }
+
+    #[test]
+    fn full_buffer_drops_oldest() {
+        let mut buffer = RewindBuffer::new(2);
+        buffer.push(snapshot(1.0));
+        buffer.push(snapshot(2.0));
+        buffer.push(snapshot(3.0));
+
+        assert_eq!(buffer.len(), 2);
+        assert_eq!(buffer.pop().unwrap().crab.x, 3.0);
+        assert_eq!(buffer.pop().unwrap().crab.x, 2.0);
+        assert!(buffer.is_empty());
+    }
+
+    #[test]
+    fn clear_empties_buffer() {
+        let mut buffer = RewindBuffer::new(5);
+        buffer.push(snapshot(1.0));
+        buffer.clear();
+
+        assert!(buffer.is_empty());
+    }
+}
@@ -5,12 +5,13 @@ Please see the file LICENSE in this distribution
 for license terms.
 */
 
-use constants::{LOG, LOG_W, MAX_DELAY, MAX_NUM_OF_LOGS, MAX_NUM_OF_TURTLES,
-                MAX_SPEED_OF_OBSTACLES, MIN_DELAY, SQUARE_SIZE, TURTLE, TURTLE_W, WIN_H, WIN_W};
+use constants::{LOG_W, MAX_DELAY, MAX_NUM_OF_LOGS, MAX_NUM_OF_TURTLES, MAX_SPEED_OF_OBSTACLES,
+                MIN_DELAY, SQUARE_SIZE, TURTLE_W, WIN_H, WIN_W};
 use ggez::graphics::Color;
-use ggez::{Context, GameResult};
-use rand::{thread_rng, Rng};
-use sprites::Rectangle;
+use rand::Rng;
+use rng::GameRng;
+use sprites::{DrawBatch, Rectangle, SpriteKind};
+use theme::Theme;
 
 /// A River Transport is what we use to represent the logs & sea turtles
 /// one would expect in the original game.
@@ -20,6 +21,19 @@ pub struct RiverTransport {
     form: Rectangle,
     speed: f32,
     direction: bool,
+    river_transport_type: u32,
+}
+
+/// Everything needed to put a River Transport back exactly where it was,
+/// used when saving and resuming a game.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct RiverTransportState {
+    pub x: f32,
+    pub y: f32,
+    pub w: f32,
+    pub speed: f32,
+    pub direction: bool,
+    pub river_transport_type: u32,
 }
 
 impl RiverTransport {
@@ -37,15 +51,32 @@ impl RiverTransport {
         let h = SQUARE_SIZE as f32;
         let x = RiverTransport::assign_starting_x(ltr_direction, w, delay);
         RiverTransport {
-            form: Rectangle::construct(
-                x,
-                y,
-                w,
-                h,
-                RiverTransport::assign_color(river_transport_type),
-            ),
+            form: Rectangle::construct(x, y, w, h),
             speed,
             direction: ltr_direction,
+            river_transport_type,
+        }
+    }
+
+    /// Rebuilds a River Transport from a saved state
+    pub fn from_state(state: &RiverTransportState) -> RiverTransport {
+        RiverTransport {
+            form: Rectangle::construct(state.x, state.y, state.w, SQUARE_SIZE),
+            speed: state.speed,
+            direction: state.direction,
+            river_transport_type: state.river_transport_type,
+        }
+    }
+
+    /// Captures the current position, speed, direction and type
+    pub fn get_state(&self) -> RiverTransportState {
+        RiverTransportState {
+            x: self.form.x,
+            y: self.form.y,
+            w: self.form.w,
+            speed: self.speed,
+            direction: self.direction,
+            river_transport_type: self.river_transport_type,
         }
     }
 
@@ -60,21 +91,24 @@ impl RiverTransport {
         }
     }
 
-    /// Assigns the appropriate color to the river transport based on the
+    /// Returns the theme's color for the river transport based on the
     /// type that has been randomly generated for the row.
-    fn assign_color(river_transport_type: u32) -> Color {
-        match river_transport_type {
-            0 => LOG,
-            _ => TURTLE,
+    fn assign_color(&self, theme: &Theme) -> Color {
+        match self.river_transport_type {
+            0 => theme.log(),
+            _ => theme.turtle(),
         }
     }
 
-    /// The draw function is provided by the ggez crate and is used
-    /// to create and display the rectangular graphic that is assigned
-    /// to the form attribute of the river transport
-    fn draw(&mut self, ctx: &mut Context) -> GameResult<()> {
-        self.form.draw(ctx)?;
-        Ok(())
+    /// Adds the log or turtles to the river layer, drawn from the sprite sheet
+    /// or as the rectangle assigned to its form if there are no sprites
+    fn draw(&self, batch: &mut DrawBatch, theme: &Theme) {
+        let kind = match self.river_transport_type {
+            0 => SpriteKind::Log,
+            _ => SpriteKind::Turtle,
+        };
+        batch.add_sprite(kind, &self.form, self.direction, self.assign_color(theme));
+        theme.mark(batch, kind, &self.form);
     }
 
     /// Updates the x-coordinate of the river transport so it
@@ -101,31 +135,41 @@ impl RiverTransport {
     /// Getter to acquire the left x-coordinate of the
     /// river transport which can be used to inform
     /// when a player is attempting to ride it.
-    pub fn get_left_edge(&mut self) -> f32 {
+    pub fn get_left_edge(&self) -> f32 {
         return self.form.x;
     }
 
     /// Getter to acquire the right x-coordinate of the
     /// river transport which can be used to inform
     /// when a player is attempting to ride it.
-    pub fn get_right_edge(&mut self) -> f32 {
+    pub fn get_right_edge(&self) -> f32 {
         return self.form.x + self.form.w;
     }
 
     /// Getter to acquire the bottom y-coordinate of the
     /// river transport which can be used to inform
     /// when a player is attempting to ride it.
-    pub fn get_bottom_edge(&mut self) -> f32 {
+    pub fn get_bottom_edge(&self) -> f32 {
         return self.form.y;
     }
 
     /// Getter to acquire the top y-coordinate of the
     /// river transport which can be used to inform
     /// when a player is attempting to ride it.
-    pub fn get_top_edge(&mut self) -> f32 {
+    pub fn get_top_edge(&self) -> f32 {
         return self.form.y - self.form.h;
     }
 
+    /// Returns the area collisions are checked against, between the edges above
+    pub fn get_hitbox(&self) -> Rectangle {
+        Rectangle::construct(
+            self.get_left_edge(),
+            self.get_top_edge(),
+            self.get_right_edge() - self.get_left_edge(),
+            self.get_bottom_edge() - self.get_top_edge(),
+        )
+    }
+
     /// Getter to acquire the speed of the
     /// river transport which can be used to inform
     /// when a player is riding a river transport
@@ -136,7 +180,7 @@ impl RiverTransport {
     /// Getter to acquire the direction of the
     /// river transport which can be used to inform
     /// when a player is riding a river transport
-    pub fn get_direction(&mut self) -> bool {
+    pub fn get_direction(&self) -> bool {
         return self.direction;
     }
 }
@@ -153,13 +197,13 @@ impl RiverLane {
     /// transportation type, speed, and the number of river transports
     /// available.  The values of these attributes are generated and
     /// used to create the river transport structs.
-    pub fn construct(y_modifier: f32) -> RiverLane {
+    pub fn construct(y_modifier: f32, rng: &mut GameRng) -> RiverLane {
         let y = WIN_H as f32 - y_modifier * SQUARE_SIZE;
-        let ltr_direction = RiverLane::generate_direction();
+        let ltr_direction = RiverLane::generate_direction(rng);
         let river_transport_type = RiverLane::generate_river_transport_type(ltr_direction);
         let num_of_river_transports =
-            RiverLane::generate_number_of_river_transports(river_transport_type);
-        let speed = RiverLane::generate_speed(river_transport_type);
+            RiverLane::generate_number_of_river_transports(river_transport_type, rng);
+        let speed = RiverLane::generate_speed(river_transport_type, rng);
         RiverLane {
             river_transports: RiverLane::create_river_transport(
                 river_transport_type,
@@ -167,10 +211,41 @@ impl RiverLane {
                 num_of_river_transports,
                 speed,
                 ltr_direction,
+                rng,
             ),
         }
     }
 
+    /// Rebuilds a River Lane from the saved states of its river transports
+    pub fn from_state(states: &[RiverTransportState]) -> RiverLane {
+        RiverLane {
+            river_transports: states.iter().map(RiverTransport::from_state).collect(),
+        }
+    }
+
+    /// Captures the state of every river transport in the river lane
+    pub fn get_state(&self) -> Vec<RiverTransportState> {
+        self.river_transports
+            .iter()
+            .map(|river_transport| river_transport.get_state())
+            .collect()
+    }
+
+    /// Returns the x-coordinate of every river transport in the river lane
+    pub fn get_positions(&self) -> Vec<f32> {
+        self.river_transports
+            .iter()
+            .map(|river_transport| river_transport.form.x)
+            .collect()
+    }
+
+    /// Moves every river transport in the river lane back to a recorded x-coordinate
+    pub fn set_positions(&mut self, positions: &[f32]) {
+        for (river_transport, x) in self.river_transports.iter_mut().zip(positions) {
+            river_transport.form.x = *x;
+        }
+    }
+
     /// Returns a vector of either logs or turtles depending on the randomly
     /// generated river transport type.
     fn create_river_transport(
@@ -179,6 +254,7 @@ impl RiverLane {
         num_of_river_transports: u32,
         speed: f32,
         ltr_direction: bool,
+        rng: &mut GameRng,
     ) -> Vec<RiverTransport> {
         match river_transport_type {
             0 => RiverLane::create_logs(
@@ -187,6 +263,7 @@ impl RiverLane {
                 speed,
                 ltr_direction,
                 river_transport_type,
+                rng,
             ),
             _ => RiverLane::create_turtles(
                 y,
@@ -194,6 +271,7 @@ impl RiverLane {
                 speed,
                 ltr_direction,
                 river_transport_type,
+                rng,
             ),
         }
     }
@@ -206,6 +284,7 @@ impl RiverLane {
         speed: f32,
         ltr_direction: bool,
         river_transport_type: u32,
+        rng: &mut GameRng,
     ) -> Vec<RiverTransport> {
         let mut logs = vec![];
         let mut delay = 0.0;
@@ -219,7 +298,7 @@ impl RiverLane {
                 river_transport_type,
             ));
 
-            delay += RiverLane::generate_log_delay(num_of_logs)
+            delay += RiverLane::generate_log_delay(num_of_logs, rng)
         }
         logs
     }
@@ -232,6 +311,7 @@ impl RiverLane {
         speed: f32,
         ltr_direction: bool,
         river_transport_type: u32,
+        rng: &mut GameRng,
     ) -> Vec<RiverTransport> {
         let mut turtles = vec![];
         let mut delay = 0.0;
@@ -245,7 +325,7 @@ impl RiverLane {
                 river_transport_type,
             ));
 
-            delay += RiverLane::generate_turtle_delay(num_of_turtles)
+            delay += RiverLane::generate_turtle_delay(num_of_turtles, rng)
         }
         turtles
     }
@@ -265,9 +345,7 @@ impl RiverLane {
     /// Uses the max number of logs and turtles constant to generate a bounded
     /// random number of river transports in a row. These max numbers are scalable
     /// depending on the width of the window.
-    fn generate_number_of_river_transports(river_transport_type: u32) -> u32 {
-        let mut rng = thread_rng();
-
+    fn generate_number_of_river_transports(river_transport_type: u32, rng: &mut GameRng) -> u32 {
         match river_transport_type {
             0 => rng.gen_range(2_u32, MAX_NUM_OF_LOGS),
             _ => rng.gen_range(2_u32, MAX_NUM_OF_TURTLES),
@@ -279,9 +357,7 @@ impl RiverLane {
     /// in a row. The turtles (_) have a slightly lower max speed to make the
     /// game easier and because they are swimming against the current--David
     /// Attenborough would be proud of these noble creatures.
-    fn generate_speed(river_transport_type: u32) -> f32 {
-        let mut rng = thread_rng();
-
+    fn generate_speed(river_transport_type: u32, rng: &mut GameRng) -> f32 {
         match river_transport_type {
             0 => rng.gen_range(0.5_f32, MAX_SPEED_OF_OBSTACLES),
             _ => rng.gen_range(0.5_f32, MAX_SPEED_OF_OBSTACLES - 0.5),
@@ -291,9 +367,7 @@ impl RiverLane {
     /// Used to generate the delay--or space--between turtles. This distance
     /// is based on the number of turtles in a row (the more items there are
     /// the less space there is to leave between them)
-    fn generate_turtle_delay(num_of_river_transports: u32) -> f32 {
-        let mut rng = thread_rng();
-
+    fn generate_turtle_delay(num_of_river_transports: u32, rng: &mut GameRng) -> f32 {
         match num_of_river_transports {
             MAX_NUM_OF_TURTLES => MIN_DELAY,
             _ => rng.gen_range(MIN_DELAY, MAX_DELAY),
@@ -303,9 +377,7 @@ impl RiverLane {
     /// Used to generate the delay--or space--between logs. This distance
     /// is based on the number of logs in a row (the more items there are
     /// the less space there is to leave between them)
-    fn generate_log_delay(num_of_river_transports: u32) -> f32 {
-        let mut rng = thread_rng();
-
+    fn generate_log_delay(num_of_river_transports: u32, rng: &mut GameRng) -> f32 {
         match num_of_river_transports {
             MAX_NUM_OF_LOGS => MIN_DELAY + (SQUARE_SIZE * 2.0),
             _ => rng.gen_range(MIN_DELAY * 2.0, MAX_DELAY * 2.0),
@@ -317,8 +389,7 @@ impl RiverLane {
     /// false). The six sided die match statement was really just to give
     /// myself a laugh. This is important and let's all be glad it's not
     /// the much respected 20 sided die.
-    fn generate_direction() -> bool {
-        let mut rng = thread_rng();
+    fn generate_direction(rng: &mut GameRng) -> bool {
         let six_sided_die: u32 = rng.gen_range(0, 99999) % 6;
         match six_sided_die {
             0 => false,
@@ -330,13 +401,11 @@ impl RiverLane {
         }
     }
 
-    /// Calls upon the draw routine for each river transport in the vector
-    pub fn draw_river_transports_in_river_lane(&mut self, ctx: &mut Context) -> GameResult<()> {
-        for river_transport in &mut self.river_transports {
-            river_transport.draw(ctx)?;
+    /// Adds each river transport in the vector to the river layer
+    pub fn draw_river_transports_in_river_lane(&self, batch: &mut DrawBatch, theme: &Theme) {
+        for river_transport in &self.river_transports {
+            river_transport.draw(batch, theme);
         }
-
-        Ok(())
     }
 
     /// Calls upon the update routine for each river transport in the vector
@@ -0,0 +1,98 @@
+/*
+Copyright (c) 2018 Matt Carnovale, Julie Rutherford-Fields, Joshua Sander
+This work is available under the "MIT License”.
+Please see the file LICENSE in this distribution
+for license terms.
+*/
+
+use rand::{thread_rng, Rng};
+
+/// The random number generator used to build the board.
+/// Unlike thread_rng its state is plain data, so it can be
+/// written to a save file and picked up exactly where it left off.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct GameRng {
+    state: [u32; 4],
+}
+
+impl GameRng {
+    /// Creates a generator whose sequence is fully determined by the seed
+    pub fn new(seed: u64) -> GameRng {
+        // Spread the seed across all four words so that
+        // small seeds still produce well mixed output
+        let mut z = seed;
+        let mut state = [0_u32; 4];
+        for word in state.iter_mut() {
+            z = z.wrapping_add(0x9E37_79B9_7F4A_7C15);
+            let mut mixed = z;
+            mixed = (mixed ^ (mixed >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+            mixed = (mixed ^ (mixed >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+            *word = (mixed ^ (mixed >> 31)) as u32;
+        }
+
+        // Xorshift gets stuck on an all zero state
+        if state == [0; 4] {
+            state[0] = 1;
+        }
+
+        GameRng { state }
+    }
+
+    /// Creates a generator from a random seed
+    pub fn from_entropy() -> GameRng {
+        GameRng::new(random_seed())
+    }
+}
+
+/// Picks a seed for a new board
+pub fn random_seed() -> u64 {
+    thread_rng().gen()
+}
+
+impl Rng for GameRng {
+    /// Xorshift128, the same algorithm rand's XorShiftRng uses
+    fn next_u32(&mut self) -> u32 {
+        let x = self.state[0];
+        let t = x ^ (x << 11);
+        self.state[0] = self.state[1];
+        self.state[1] = self.state[2];
+        self.state[2] = self.state[3];
+        let w = self.state[3];
+        self.state[3] = w ^ (w >> 19) ^ (t ^ (t >> 8));
+        self.state[3]
+    }
+}
+
+/// Unit tests for GameRng.
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    #[test]
+    fn same_seed_gives_same_sequence() {
+        let mut first = GameRng::new(42);
+        let mut second = GameRng::new(42);
+
+        for _ in 0..100 {
+            assert_eq!(first.next_u32(), second.next_u32());
+        }
+    }
+
+    #[test]
+    fn different_seeds_give_different_sequences() {
+        let mut first = GameRng::new(1);
+        let mut second = GameRng::new(2);
+
+        assert_ne!(first.next_u32(), second.next_u32());
+    }
+
+    #[test]
+    fn cloned_state_resumes_sequence() {
+        let mut rng = GameRng::new(7);
+        rng.next_u32();
+        let mut saved = rng.clone();
+
+        assert_eq!(rng.gen_range(0_u32, 1000), saved.gen_range(0_u32, 1000));
+    }
+}
@@ -0,0 +1,75 @@
+/*
+Copyright (c) 2018 Matt Carnovale, Julie Rutherford-Fields, Joshua Sander
+This work is available under the "MIT License”.
+Please see the file LICENSE in this distribution
+for license terms.
+*/
+
+use characters::CrabState;
+use constants::SAVE_FILE;
+use ggez::{Context, GameError, GameResult};
+use replay::Replay;
+use river::RiverTransportState;
+use rng::GameRng;
+use serde_json;
+use std::io::{Read, Write};
+use traffic::VehicleState;
+use turns::Turns;
+
+/// A snapshot of an in-progress game. It is written to the user's
+/// config directory and restored from the "Continue" menu entry.
+#[derive(Serialize, Deserialize)]
+pub struct SaveGame {
+    pub crab: CrabState,
+    pub lanes: Vec<Vec<VehicleState>>,
+    pub river_lanes: Vec<Vec<RiverTransportState>>,
+    pub cubbies: Vec<bool>,
+    pub rng: GameRng,
+    /// Whose turn it is and the waiting player, in a two player game
+    #[serde(default)]
+    pub turns: Option<Turns>,
+    /// The second crab, in a co-op game
+    #[serde(default)]
+    pub partner: Option<CrabState>,
+    /// The seed the board was first built from, recorded with the final score
+    #[serde(default)]
+    pub seed: u64,
+    /// The level being played, starting from 1
+    #[serde(default)]
+    pub level: u32,
+    /// Every hop made so far, so the final score can be checked
+    #[serde(default)]
+    pub replay: Replay,
+}
+
+impl SaveGame {
+    /// Returns true if there is a saved game to continue
+    pub fn exists(ctx: &Context) -> bool {
+        ctx.filesystem.exists(SAVE_FILE)
+    }
+
+    /// Writes the save game to disk, replacing any previous one
+    pub fn write(&self, ctx: &mut Context) -> GameResult<()> {
+        let json =
+            serde_json::to_string(self).map_err(|e| GameError::UnknownError(e.to_string()))?;
+        let mut file = ctx.filesystem.create(SAVE_FILE)?;
+        file.write_all(json.as_bytes())?;
+        Ok(())
+    }
+
+    /// Reads the save game back from disk
+    pub fn load(ctx: &mut Context) -> GameResult<SaveGame> {
+        let mut json = String::new();
+        let mut file = ctx.filesystem.open(SAVE_FILE)?;
+        file.read_to_string(&mut json)?;
+        serde_json::from_str(&json).map_err(|e| GameError::UnknownError(e.to_string()))
+    }
+
+    /// Removes the save game so a finished game can't be continued
+    pub fn delete(ctx: &mut Context) -> GameResult<()> {
+        if SaveGame::exists(ctx) {
+            ctx.filesystem.delete(SAVE_FILE)?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,121 @@
+/*
+Copyright (c) 2018 Matt Carnovale, Julie Rutherford-Fields, Joshua Sander
+This work is available under the "MIT License”.
+Please see the file LICENSE in this distribution
+for license terms.
+*/
+
+use constants::{DEFAULT_JOIN_ADDRESS, DEFAULT_LEADERBOARD_ADDRESS, DEFAULT_PLAYER_NAME,
+                DEFAULT_SPECTATE_ADDRESS, DEFAULT_THEME, DEFAULT_VOLUME, SETTINGS_FILE};
+use controls::Action;
+use ggez::{Context, GameError, GameResult};
+use serde_json;
+use std::collections::HashMap;
+use std::io::{Read, Write};
+use theme::ColorMode;
+
+/// Player preferences which are kept between games
+/// in the user's config directory
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Settings {
+    pub music_volume: f32,
+    pub effects_volume: f32,
+    /// Key names for each action, any action left out keeps its default keys
+    pub key_bindings: HashMap<Action, Vec<String>>,
+    /// Host and port that "Join Game" connects to
+    pub join_address: String,
+    /// Whether games are published for others to spectate
+    pub broadcast: bool,
+    /// Host and port that "Spectate" connects to
+    pub spectate_address: String,
+    /// Name high scores are recorded under
+    pub player_name: String,
+    /// Host and port of the leaderboard server
+    pub leaderboard_address: String,
+    /// Name of the theme file the game is drawn with
+    pub theme: String,
+    /// Accessibility palette put over the theme
+    pub color_mode: ColorMode,
+}
+
+impl Default for Settings {
+    fn default() -> Settings {
+        Settings {
+            music_volume: DEFAULT_VOLUME,
+            effects_volume: DEFAULT_VOLUME,
+            key_bindings: HashMap::new(),
+            join_address: DEFAULT_JOIN_ADDRESS.to_string(),
+            broadcast: false,
+            spectate_address: DEFAULT_SPECTATE_ADDRESS.to_string(),
+            player_name: DEFAULT_PLAYER_NAME.to_string(),
+            leaderboard_address: DEFAULT_LEADERBOARD_ADDRESS.to_string(),
+            theme: DEFAULT_THEME.to_string(),
+            color_mode: ColorMode::Standard,
+        }
+    }
+}
+
+impl Settings {
+    /// Reads the settings from disk, falling back to the
+    /// defaults if there are none yet or they can't be read
+    pub fn load(ctx: &mut Context) -> Settings {
+        if !ctx.filesystem.exists(SETTINGS_FILE) {
+            return Settings::default();
+        }
+        match Settings::read(ctx) {
+            Ok(settings) => settings,
+            Err(e) => {
+                println!("Could not read settings, using defaults: {}", e);
+                Settings::default()
+            }
+        }
+    }
+
+    /// Parses the settings file
+    fn read(ctx: &mut Context) -> GameResult<Settings> {
+        let mut json = String::new();
+        let mut file = ctx.filesystem.open(SETTINGS_FILE)?;
+        file.read_to_string(&mut json)?;
+        serde_json::from_str(&json).map_err(|e| GameError::UnknownError(e.to_string()))
+    }
+
+    /// Writes the settings to disk
+    pub fn save(&self, ctx: &mut Context) -> GameResult<()> {
+        let json = serde_json::to_string_pretty(self)
+            .map_err(|e| GameError::UnknownError(e.to_string()))?;
+        let mut file = ctx.filesystem.create(SETTINGS_FILE)?;
+        file.write_all(json.as_bytes())?;
+        Ok(())
+    }
+}
+
+/// Moves a volume up or down by one step, keeping it between 0.0 and 1.0
+pub fn step_volume(volume: f32, up: bool) -> f32 {
+    let stepped = if up { volume + 0.1 } else { volume - 0.1 };
+    // Round to a tenth so repeated steps don't drift
+    ((stepped.max(0.0).min(1.0)) * 10.0).round() / 10.0
+}
+
+/// Unit tests for settings helpers.
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    #[test]
+    fn step_volume_stays_in_range() {
+        assert_eq!(step_volume(1.0, true), 1.0);
+        assert_eq!(step_volume(0.0, false), 0.0);
+        assert_eq!(step_volume(0.5, true), 0.6);
+        assert_eq!(step_volume(0.5, false), 0.4);
+    }
+
+    #[test]
+    fn missing_fields_use_defaults() {
+        let settings: Settings = serde_json::from_str("{\"music_volume\": 0.2}").unwrap();
+
+        assert_eq!(settings.music_volume, 0.2);
+        assert_eq!(settings.effects_volume, DEFAULT_VOLUME);
+    }
+}
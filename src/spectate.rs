@@ -0,0 +1,169 @@
+/*
+Copyright (c) 2018 Matt Carnovale, Julie Rutherford-Fields, Joshua Sander
+This work is available under the "MIT License”.
+Please see the file LICENSE in this distribution
+for license terms.
+*/
+
+use characters::CrabState;
+use constants::{NET_CONNECT_TIMEOUT_MS, SPECTATE_MAX_LINE_BYTES};
+use net::JsonLines;
+use river::RiverTransportState;
+use std::io::{self, ErrorKind};
+use std::net::{TcpListener, TcpStream, ToSocketAddrs};
+use std::time::Duration;
+use traffic::VehicleState;
+
+/// Everything a spectator needs to draw one frame of someone else's game
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct SpectatorFrame {
+    /// Every crab on the board, player one first
+    pub crabs: Vec<CrabState>,
+    pub lanes: Vec<Vec<VehicleState>>,
+    pub river_lanes: Vec<Vec<RiverTransportState>>,
+    pub cubbies: Vec<bool>,
+}
+
+/// Publishes each frame of a running game to anyone spectating it
+pub struct Broadcaster {
+    listener: TcpListener,
+    viewers: Vec<JsonLines>,
+}
+
+impl Broadcaster {
+    /// Starts listening for spectators on this machine only
+    pub fn new(port: u16) -> io::Result<Broadcaster> {
+        let listener = TcpListener::bind(("127.0.0.1", port))?;
+        listener.set_nonblocking(true)?;
+        Ok(Broadcaster {
+            listener: listener,
+            viewers: vec![],
+        })
+    }
+
+    /// Returns how many spectators are watching
+    pub fn viewers(&self) -> usize {
+        self.viewers.len()
+    }
+
+    /// Lets in any new spectators and sends the frame to all of them.
+    /// A spectator still catching up on the last frame skips this one
+    /// rather than falling further behind, and one that has gone away
+    /// is dropped. Never waits on the network.
+    pub fn publish(&mut self, frame: &SpectatorFrame) {
+        loop {
+            match self.listener.accept() {
+                Ok((stream, _)) => match JsonLines::new(stream, SPECTATE_MAX_LINE_BYTES) {
+                    Ok(viewer) => self.viewers.push(viewer),
+                    Err(e) => println!("Could not add spectator: {}", e),
+                },
+                Err(ref e) if e.kind() == ErrorKind::WouldBlock => break,
+                Err(e) => {
+                    println!("Could not accept spectator: {}", e);
+                    break;
+                }
+            }
+        }
+
+        let mut viewers = vec![];
+        for mut viewer in self.viewers.drain(..) {
+            let sent = if viewer.is_flushed() {
+                viewer.send(frame)
+            } else {
+                viewer.flush()
+            };
+            if sent.is_ok() {
+                viewers.push(viewer);
+            }
+        }
+        self.viewers = viewers;
+    }
+}
+
+/// A read-only view of a game being broadcast from another Crabber
+pub struct Spectator {
+    lines: JsonLines,
+}
+
+impl Spectator {
+    /// Connects to a broadcasting game, giving up after a few seconds
+    pub fn connect(address: &str) -> io::Result<Spectator> {
+        let address = address
+            .to_socket_addrs()?
+            .next()
+            .ok_or_else(|| io::Error::new(ErrorKind::InvalidInput, "no address to connect to"))?;
+        let timeout = Duration::from_millis(NET_CONNECT_TIMEOUT_MS);
+        let stream = TcpStream::connect_timeout(&address, timeout)?;
+        Ok(Spectator {
+            lines: JsonLines::new(stream, SPECTATE_MAX_LINE_BYTES)?,
+        })
+    }
+
+    /// Reads whatever frames have arrived without waiting for more.
+    /// Returns the newest one, or None if nothing new has arrived.
+    pub fn receive(&mut self) -> io::Result<Option<SpectatorFrame>> {
+        let frames: Vec<SpectatorFrame> = self.lines.receive()?;
+        Ok(frames.into_iter().last())
+    }
+}
+
+/// Unit tests for Broadcaster and Spectator, run over a real connection on localhost.
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use background::Cubbies;
+    use characters::Crab;
+    use constants::{START, WIN_W};
+    use std::thread;
+
+    fn frame(score: isize) -> SpectatorFrame {
+        let mut crab = Crab::new(WIN_W, START as u32);
+        crab.add_to_score(score);
+        SpectatorFrame {
+            crabs: vec![crab.get_state()],
+            lanes: vec![],
+            river_lanes: vec![],
+            cubbies: Cubbies::construct().get_state(),
+        }
+    }
+
+    #[test]
+    fn spectator_sees_the_newest_frame() {
+        let mut broadcaster = Broadcaster::new(0).unwrap();
+        let port = broadcaster.listener.local_addr().unwrap().port();
+        let mut spectator = Spectator::connect(&format!("127.0.0.1:{}", port)).unwrap();
+
+        while broadcaster.viewers() == 0 {
+            broadcaster.publish(&frame(100));
+            thread::sleep(Duration::from_millis(1));
+        }
+
+        // Frames can arrive together, only the newest is kept
+        let mut score = 0;
+        while score != 200 {
+            broadcaster.publish(&frame(200));
+            thread::sleep(Duration::from_millis(1));
+            if let Some(latest) = spectator.receive().unwrap() {
+                score = latest.crabs[0].score;
+            }
+        }
+    }
+
+    #[test]
+    fn spectator_leaving_is_dropped() {
+        let mut broadcaster = Broadcaster::new(0).unwrap();
+        let port = broadcaster.listener.local_addr().unwrap().port();
+        let spectator = Spectator::connect(&format!("127.0.0.1:{}", port)).unwrap();
+        while broadcaster.viewers() == 0 {
+            broadcaster.publish(&frame(0));
+            thread::sleep(Duration::from_millis(1));
+        }
+
+        drop(spectator);
+        while broadcaster.viewers() > 0 {
+            broadcaster.publish(&frame(0));
+            thread::sleep(Duration::from_millis(1));
+        }
+    }
+}
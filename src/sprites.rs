@@ -5,8 +5,34 @@ Please see the file LICENSE in this distribution
 for license terms.
 */
 
-use ggez::graphics::{self, set_color, Color, DrawMode};
+use characters::DeathCause;
+use constants::{HOP_FRAMES, PATTERN_MARK, SQUARE_SIZE};
+use ggez::graphics::spritebatch::SpriteBatch;
+use ggez::graphics::{self, Color, DrawMode, DrawParam, FilterMode, Image, MeshBuilder,
+                     Point2, Rect};
 use ggez::{Context, GameResult};
+use std::f32::consts::PI;
+
+/// The way the crab sprite faces, which is the way it last hopped
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Facing {
+    Up,
+    Down,
+    Left,
+    Right,
+}
+
+impl Facing {
+    /// Returns how far the sprite is turned from facing up, in radians
+    fn rotation(&self) -> f32 {
+        match *self {
+            Facing::Up => 0.0,
+            Facing::Right => PI / 2.0,
+            Facing::Down => PI,
+            Facing::Left => -PI / 2.0,
+        }
+    }
+}
 
 /// Represents the crab sprite graphic
 pub struct CrabSprite {
@@ -14,6 +40,12 @@ pub struct CrabSprite {
     pub y: f32,
     pub w: f32,
     pub h: f32,
+    pub tint: Color,
+    pub facing: Facing,
+    /// How far the crab was from where it is now when it started hopping
+    hop_from: Point2,
+    /// Frames left until the hop lands
+    hop_frames: u32,
 }
 
 /// Implements a new CrabSprite
@@ -25,17 +57,178 @@ impl CrabSprite {
             y: y,
             w: w,
             h: h,
+            tint: graphics::WHITE,
+            facing: Facing::Up,
+            hop_from: Point2::origin(),
+            hop_frames: 0,
         }
     }
 
-    /// Draws CrabSprite graphic onscreen
-    pub fn draw(&mut self, ctx: &mut Context) -> GameResult<()> {
-        let image_small_crab = graphics::Image::new(ctx, "/tiny_crab.png")?;
-        let dest_point = graphics::Point2::new(self.x, self.y);
-        graphics::draw(ctx, &image_small_crab, dest_point, 0.0)?;
+    /// Starts the hop to where the sprite is now from where it was.
+    /// Only the drawing is tweened, the position has already moved.
+    pub fn start_hop(&mut self, from_x: f32, from_y: f32) {
+        self.hop_from = Point2::new(from_x - self.x, from_y - self.y);
+        self.hop_frames = HOP_FRAMES;
+    }
+
+    /// Lands any hop in progress straight away, for when the crab is moved
+    /// somewhere without hopping there
+    pub fn stop_hop(&mut self) {
+        self.hop_frames = 0;
+    }
+
+    /// Returns true while a hop is still being drawn
+    pub fn is_hopping(&self) -> bool {
+        self.hop_frames > 0
+    }
+
+    /// Moves the hop on by a frame
+    pub fn animate(&mut self) {
+        if self.hop_frames > 0 {
+            self.hop_frames -= 1;
+        }
+    }
+
+    /// Draws CrabSprite graphic onscreen, turned the way it faces and
+    /// part of the way through any hop it is making
+    pub fn draw(&mut self, ctx: &mut Context, image: &Image) -> GameResult<()> {
+        // Ease out of the square hopped from, slowing as the crab lands
+        let t = 1.0 - self.hop_frames as f32 / HOP_FRAMES as f32;
+        let remaining = (1.0 - t) * (1.0 - t);
+        let x = self.x + self.hop_from.x * remaining;
+        let y = self.y + self.hop_from.y * remaining;
+
+        // Stretch along the hop in mid-air and squash back down to land.
+        // The sprite is turned first, so its own y is always the way it faces.
+        let stretch = if self.is_hopping() {
+            (t * PI).sin() * 0.25
+        } else {
+            0.0
+        };
+
+        // Turn about the middle of the sprite rather than its corner
+        let dest_point = graphics::Point2::new(
+            x + image.width() as f32 / 2.0,
+            y + image.height() as f32 / 2.0,
+        );
+        graphics::draw_ex(
+            ctx,
+            image,
+            DrawParam {
+                dest: dest_point,
+                rotation: self.facing.rotation(),
+                offset: Point2::new(0.5, 0.5),
+                scale: Point2::new(1.0 - stretch / 2.0, 1.0 + stretch),
+                color: Some(self.tint),
+                ..Default::default()
+            },
+        )?;
 
         Ok(())
     }
+
+    /// Draws one frame of the death animation for the given cause, where
+    /// the crab died. Progress runs from 0.0 at the moment of death to 1.0
+    /// at respawn.
+    pub fn draw_death(
+        &mut self,
+        ctx: &mut Context,
+        image: &Image,
+        cause: DeathCause,
+        progress: f32,
+    ) -> GameResult<()> {
+        let center = Point2::new(
+            self.x + image.width() as f32 / 2.0,
+            self.y + image.height() as f32 / 2.0,
+        );
+
+        // Lay down what is left behind under the crab
+        match cause {
+            DeathCause::Vehicle => self.draw_skid_mark(ctx, center, progress)?,
+            DeathCause::Drowned | DeathCause::CarriedOffscreen => {
+                self.draw_ripples(ctx, center, progress)?
+            }
+            DeathCause::CubbyWall | DeathCause::Timeout => {}
+        }
+
+        // Each cause flattens, sinks or shakes the crab in its own way
+        let (scale_x, scale_y, shake, color) = match cause {
+            DeathCause::Vehicle => {
+                // Flattened almost at once by the impact
+                let squash = (progress * 4.0).min(1.0);
+                (
+                    1.0 + squash * 0.5,
+                    1.0 - squash * 0.8,
+                    0.0,
+                    Color::new(1.0, 0.4, 0.4, 1.0),
+                )
+            }
+            DeathCause::Drowned | DeathCause::CarriedOffscreen => (
+                1.0 - progress * 0.7,
+                1.0 - progress * 0.7,
+                0.0,
+                Color::new(0.5, 0.5, 1.0, 1.0 - progress),
+            ),
+            DeathCause::CubbyWall => (
+                1.0,
+                1.0,
+                (progress * 40.0).sin() * 3.0 * (1.0 - progress),
+                Color::new(1.0, 1.0, 0.6, 1.0),
+            ),
+            DeathCause::Timeout => (1.0, 1.0, 0.0, Color::new(0.6, 0.6, 0.6, 1.0 - progress)),
+        };
+
+        // Mix in the crab's own tint so players can still tell whose crab died
+        let color = Color::new(
+            color.r * self.tint.r,
+            color.g * self.tint.g,
+            color.b * self.tint.b,
+            color.a * self.tint.a,
+        );
+
+        // Keep the crab turned the way it was and centered on the square it died in
+        graphics::draw_ex(
+            ctx,
+            image,
+            DrawParam {
+                dest: Point2::new(center.x + shake, center.y),
+                rotation: self.facing.rotation(),
+                offset: Point2::new(0.5, 0.5),
+                scale: Point2::new(scale_x, scale_y),
+                color: Some(color),
+                ..Default::default()
+            },
+        )?;
+
+        Ok(())
+    }
+
+    /// Draws the skid mark a vehicle leaves across the square, spreading out
+    /// either side of the crab
+    fn draw_skid_mark(&self, ctx: &mut Context, center: Point2, progress: f32) -> GameResult<()> {
+        let spread = (progress * 3.0).min(1.0) * self.w * 1.5;
+        graphics::set_color(ctx, Color::new(0.1, 0.1, 0.1, 0.8 * (1.0 - progress / 2.0)))?;
+        for offset in &[-self.h / 4.0, self.h / 4.0] {
+            let mark = Rect::new(center.x - spread, center.y + offset - 2.0, spread * 2.0, 4.0);
+            graphics::rectangle(ctx, DrawMode::Fill, mark)?;
+        }
+        Ok(())
+    }
+
+    /// Draws rings spreading out across the water from where the crab went under
+    fn draw_ripples(&self, ctx: &mut Context, center: Point2, progress: f32) -> GameResult<()> {
+        for ring in 0..3 {
+            // Each ring starts a little after the one inside it
+            let age = progress - ring as f32 * 0.2;
+            if age <= 0.0 {
+                continue;
+            }
+            let radius = self.w / 4.0 + age * self.w;
+            graphics::set_color(ctx, Color::new(0.8, 0.9, 1.0, 1.0 - age))?;
+            graphics::circle(ctx, DrawMode::Line(2.0), center, radius, 1.0)?;
+        }
+        Ok(())
+    }
 }
 
 /// Represents a Rectangle object
@@ -44,33 +237,328 @@ pub struct Rectangle {
     pub y: f32,
     pub w: f32,
     pub h: f32,
-    pub colour: graphics::Color, //ggez Color struct: r: f32  g: f32  b: f32  a: f32
 }
 
 /// Implements a Rectangle object
 impl Rectangle {
     /// Constructs a Rectangle object
-    pub fn construct(x: f32, y: f32, w: f32, h: f32, colour: Color) -> Rectangle {
+    pub fn construct(x: f32, y: f32, w: f32, h: f32) -> Rectangle {
         Rectangle {
             x: x,
             y: y,
             w: w,
             h: h,
-            colour: colour,
         }
     }
 
-    /// Draws the Rectangle object to the screen
+}
+
+/// The kinds of lane object that have their own art on the sprite sheet
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum SpriteKind {
+    Car,
+    Truck,
+    Log,
+    Turtle,
+}
+
+/// The art for the traffic and river transports, all kept on one image.
+/// Everything on the sheet faces right and is mirrored to face left.
+#[derive(Clone)]
+pub struct SpriteSheet {
+    image: Image,
+}
+
+impl SpriteSheet {
+    /// Loads the sprite sheet at the given path, or returns None if it
+    /// can't be loaded so that colored rectangles are drawn instead
+    pub fn load(ctx: &mut Context, path: &str) -> Option<SpriteSheet> {
+        match Image::new(ctx, path) {
+            Ok(mut image) => {
+                // Keep the pixel art crisp when it is stretched
+                image.set_filter(FilterMode::Nearest);
+                Some(SpriteSheet { image })
+            }
+            Err(e) => {
+                println!("Could not load sprites, drawing rectangles instead: {}", e);
+                None
+            }
+        }
+    }
+
+    /// Works out the pieces of the sheet that cover a lane object's rectangle,
+    /// facing the way it is going. Cars and trucks are tinted with the given
+    /// color. Logs are stretched between their end caps and turtles are drawn
+    /// side by side to fill the space.
+    fn params(
+        &self,
+        kind: SpriteKind,
+        form: &Rectangle,
+        facing_right: bool,
+        colour: Color,
+    ) -> Vec<DrawParam> {
+        // Each piece is its cell on the sheet, and where and how wide it is drawn
+        let mut pieces = vec![];
+        let tint = match kind {
+            SpriteKind::Car => {
+                pieces.push((Rect::new(0.0, 0.0, 2.0, 1.0), 0.0, form.w));
+                colour
+            }
+            SpriteKind::Truck => {
+                pieces.push((Rect::new(0.0, 1.0, 4.0, 1.0), 0.0, form.w));
+                colour
+            }
+            SpriteKind::Log => {
+                let middle = (form.w - SQUARE_SIZE * 2.0).max(0.0);
+                pieces.push((Rect::new(0.0, 2.0, 1.0, 1.0), 0.0, SQUARE_SIZE));
+                pieces.push((Rect::new(1.0, 2.0, 1.0, 1.0), SQUARE_SIZE, middle));
+                pieces.push((Rect::new(2.0, 2.0, 1.0, 1.0), SQUARE_SIZE + middle, SQUARE_SIZE));
+                graphics::WHITE
+            }
+            SpriteKind::Turtle => {
+                let mut offset = 0.0;
+                while offset + SQUARE_SIZE <= form.w {
+                    pieces.push((Rect::new(3.0, 2.0, 1.0, 1.0), offset, SQUARE_SIZE));
+                    offset += SQUARE_SIZE;
+                }
+                graphics::WHITE
+            }
+        };
+
+        let sheet_w = self.image.width() as f32 / SQUARE_SIZE;
+        let sheet_h = self.image.height() as f32 / SQUARE_SIZE;
+        let mut params = vec![];
+        for (cell, offset, w) in pieces {
+            // Facing left mirrors both the pieces and where they go
+            let (x, scale_x) = if facing_right {
+                (form.x + offset, w / (cell.w * SQUARE_SIZE))
+            } else {
+                (form.x + form.w - offset, -w / (cell.w * SQUARE_SIZE))
+            };
+            params.push(DrawParam {
+                src: Rect::new(
+                    cell.x / sheet_w,
+                    cell.y / sheet_h,
+                    cell.w / sheet_w,
+                    cell.h / sheet_h,
+                ),
+                dest: Point2::new(x, form.y),
+                scale: Point2::new(scale_x, form.h / SQUARE_SIZE),
+                color: Some(tint),
+                ..Default::default()
+            });
+        }
+        params
+    }
+}
+
+/// Collects everything on one layer of the board, like the traffic or the
+/// river, so the whole layer is drawn in a few draw calls rather than one
+/// per object. Rectangles are built into one mesh per color and sprites go
+/// into a single batch. Objects are added again every frame.
+pub struct DrawBatch {
+    rectangles: Vec<(Color, MeshBuilder)>,
+    /// Patterns and outlines, which go over the sprites
+    markings: Vec<(Color, MeshBuilder)>,
+    sprites: Option<(SpriteSheet, SpriteBatch)>,
+    has_sprites: bool,
+}
+
+impl DrawBatch {
+    /// Creates an empty layer. Without a sprite sheet every
+    /// sprite added to it is drawn as its rectangle instead.
+    pub fn new(sheet: Option<&SpriteSheet>) -> DrawBatch {
+        DrawBatch {
+            rectangles: vec![],
+            markings: vec![],
+            sprites: sheet.map(|sheet| (sheet.clone(), SpriteBatch::new(sheet.image.clone()))),
+            has_sprites: false,
+        }
+    }
+
+    /// Returns how many draw calls the layer will take
+    pub fn draw_calls(&self) -> usize {
+        self.rectangles.len() + self.markings.len() + if self.has_sprites { 1 } else { 0 }
+    }
+
+    /// Adds a filled rectangle
+    pub fn add_rectangle(&mut self, form: &Rectangle, colour: Color) {
+        add_polygon(&mut self.rectangles, colour, DrawMode::Fill, &corners(form, 0.0));
+    }
+
+    /// Adds a border around the inside edge of a rectangle, drawn
+    /// over the sprites
+    pub fn add_outline(&mut self, form: &Rectangle, colour: Color, width: f32) {
+        let points = corners(form, width / 2.0);
+        add_polygon(&mut self.markings, colour, DrawMode::Line(width), &points);
+    }
+
+    /// Adds a pattern over a lane object, drawn over the sprites, so each
+    /// kind can be told apart without relying on color. Vehicles get
+    /// upright stripes, logs get lengthwise grain and turtles get spots.
+    pub fn add_pattern(&mut self, kind: SpriteKind, form: &Rectangle, colour: Color) {
+        let mut marks = vec![];
+        match kind {
+            SpriteKind::Car | SpriteKind::Truck => {
+                let mut x = form.x + PATTERN_MARK;
+                while x + PATTERN_MARK < form.x + form.w {
+                    marks.push(Rectangle::construct(
+                        x,
+                        form.y + PATTERN_MARK,
+                        PATTERN_MARK,
+                        form.h - 2.0 * PATTERN_MARK,
+                    ));
+                    x += 3.0 * PATTERN_MARK;
+                }
+            }
+            SpriteKind::Log => for row in 1..3 {
+                marks.push(Rectangle::construct(
+                    form.x + PATTERN_MARK,
+                    form.y + form.h * row as f32 / 3.0 - PATTERN_MARK / 2.0,
+                    form.w - 2.0 * PATTERN_MARK,
+                    PATTERN_MARK,
+                ));
+            },
+            SpriteKind::Turtle => {
+                let spot = 2.0 * PATTERN_MARK;
+                let mut x = form.x + SQUARE_SIZE / 2.0 - spot / 2.0;
+                while x + spot <= form.x + form.w {
+                    marks.push(Rectangle::construct(x, form.y + form.h / 2.0 - spot / 2.0, spot, spot));
+                    x += SQUARE_SIZE;
+                }
+            }
+        }
+        for mark in &marks {
+            add_polygon(&mut self.markings, colour, DrawMode::Fill, &corners(mark, 0.0));
+        }
+    }
+
+    /// Adds a lane object from the sprite sheet over the area of its
+    /// rectangle, or the rectangle itself in the given color if there
+    /// is no sprite sheet
+    pub fn add_sprite(
+        &mut self,
+        kind: SpriteKind,
+        form: &Rectangle,
+        facing_right: bool,
+        colour: Color,
+    ) {
+        match self.sprites {
+            Some((ref sheet, ref mut batch)) => {
+                for param in sheet.params(kind, form, facing_right, colour) {
+                    batch.add(param);
+                }
+                self.has_sprites = true;
+            }
+            None => self.add_rectangle(form, colour),
+        }
+    }
+
+    /// Draws everything added since the last draw and empties the layer
     pub fn draw(&mut self, ctx: &mut Context) -> GameResult<()> {
-        // Sets the color for the object
-        set_color(ctx, self.colour)?;
+        for (colour, mesh) in self.rectangles.drain(..) {
+            let mesh = mesh.build(ctx)?;
+            graphics::draw_ex(
+                ctx,
+                &mesh,
+                DrawParam {
+                    color: Some(colour),
+                    ..Default::default()
+                },
+            )?;
+        }
 
-        let rectangle = graphics::Rect::new(self.x, self.y, self.w, self.h);
+        if let Some((_, ref mut batch)) = self.sprites {
+            if self.has_sprites {
+                graphics::draw_ex(
+                    ctx,
+                    batch,
+                    DrawParam {
+                        color: Some(graphics::WHITE),
+                        ..Default::default()
+                    },
+                )?;
+                batch.clear();
+            }
+        }
+        self.has_sprites = false;
 
-        // Draws a rectangle. DrawMode specifies whether
-        // a shape should be drawn filled or as an outline.
-        graphics::rectangle(ctx, DrawMode::Fill, rectangle)?;
+        for (colour, mesh) in self.markings.drain(..) {
+            let mesh = mesh.build(ctx)?;
+            graphics::draw_ex(
+                ctx,
+                &mesh,
+                DrawParam {
+                    color: Some(colour),
+                    ..Default::default()
+                },
+            )?;
+        }
 
         Ok(())
     }
 }
+
+/// Returns the corners of a rectangle shrunk by the given amount on every side
+fn corners(form: &Rectangle, inset: f32) -> [Point2; 4] {
+    [
+        Point2::new(form.x + inset, form.y + inset),
+        Point2::new(form.x + form.w - inset, form.y + inset),
+        Point2::new(form.x + form.w - inset, form.y + form.h - inset),
+        Point2::new(form.x + inset, form.y + form.h - inset),
+    ]
+}
+
+/// Adds a polygon to the mesh for its color, so everything
+/// of one color is drawn in a single call
+fn add_polygon(
+    meshes: &mut Vec<(Color, MeshBuilder)>,
+    colour: Color,
+    mode: DrawMode,
+    points: &[Point2],
+) {
+    if let Some(&mut (_, ref mut mesh)) = meshes
+        .iter_mut()
+        .find(|&&mut (existing, _)| existing == colour)
+    {
+        mesh.polygon(mode, points);
+        return;
+    }
+    let mut mesh = MeshBuilder::new();
+    mesh.polygon(mode, points);
+    meshes.push((colour, mesh));
+}
+
+/// Unit tests for DrawBatch.
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    #[test]
+    fn rectangles_of_one_color_share_a_draw_call() {
+        let mut batch = DrawBatch::new(None);
+        let red = Color::new(1.0, 0.0, 0.0, 1.0);
+        let blue = Color::new(0.0, 0.0, 1.0, 1.0);
+
+        batch.add_rectangle(&Rectangle::construct(0.0, 0.0, 10.0, 10.0), red);
+        batch.add_rectangle(&Rectangle::construct(20.0, 0.0, 10.0, 10.0), blue);
+        let car = Rectangle::construct(40.0, 0.0, 10.0, 10.0);
+        batch.add_sprite(SpriteKind::Car, &car, true, red);
+
+        assert_eq!(batch.draw_calls(), 2);
+    }
+
+    #[test]
+    fn markings_are_drawn_separately_from_rectangles() {
+        let mut batch = DrawBatch::new(None);
+        let black = Color::new(0.0, 0.0, 0.0, 1.0);
+        let log = Rectangle::construct(0.0, 0.0, 120.0, SQUARE_SIZE);
+
+        batch.add_rectangle(&log, black);
+        batch.add_pattern(SpriteKind::Log, &log, black);
+        batch.add_outline(&log, black, 3.0);
+
+        assert_eq!(batch.draw_calls(), 2);
+    }
+}
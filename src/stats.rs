@@ -0,0 +1,56 @@
+/*
+Copyright (c) 2018 Matt Carnovale, Julie Rutherford-Fields, Joshua Sander
+This work is available under the "MIT License”.
+Please see the file LICENSE in this distribution
+for license terms.
+*/
+
+use characters::DeathCounts;
+use events::{GameEvent, GameEventListener};
+
+/// Totals for the current session, built up from game events
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct GameStats {
+    pub hops: u32,
+    pub rows_reached: u32,
+    pub deaths: DeathCounts,
+    pub cubbies_filled: u32,
+    pub levels_cleared: u32,
+    pub games_over: u32,
+}
+
+impl GameEventListener for GameStats {
+    fn on_event(&mut self, event: &GameEvent) {
+        match *event {
+            GameEvent::Hopped => self.hops += 1,
+            GameEvent::ReachedNewRow => self.rows_reached += 1,
+            GameEvent::Died(cause) => self.deaths.add(cause),
+            GameEvent::CubbyFilled(_) => self.cubbies_filled += 1,
+            GameEvent::LevelCleared => self.levels_cleared += 1,
+            GameEvent::GameOver => self.games_over += 1,
+            GameEvent::ExtraLife => {}
+        }
+    }
+}
+
+/// Unit tests for GameStats.
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use characters::DeathCause;
+
+    #[test]
+    fn events_are_tallied() {
+        let mut stats = GameStats::default();
+
+        stats.on_event(&GameEvent::Hopped);
+        stats.on_event(&GameEvent::Hopped);
+        stats.on_event(&GameEvent::Died(DeathCause::Drowned));
+        stats.on_event(&GameEvent::CubbyFilled(0));
+
+        assert_eq!(stats.hops, 2);
+        assert_eq!(stats.deaths.get(DeathCause::Drowned), 1);
+        assert_eq!(stats.cubbies_filled, 1);
+    }
+}
@@ -0,0 +1,334 @@
+/*
+Copyright (c) 2018 Matt Carnovale, Julie Rutherford-Fields, Joshua Sander
+This work is available under the "MIT License”.
+Please see the file LICENSE in this distribution
+for license terms.
+*/
+
+use constants::{DEFAULT_THEME, THEMES_DIR};
+use ggez::graphics::{self, Color};
+use ggez::{Context, GameError, GameResult};
+use serde_json;
+use sprites::{DrawBatch, Rectangle, SpriteKind};
+use std::io::Read;
+
+/// The colors the game is drawn in, and optionally images to use instead
+/// of the usual ones. Themes are JSON files in the themes directory of the
+/// resources, with colors written as [red, green, blue, alpha] from 0 to 1.
+/// Anything a theme leaves out is taken from the classic colors.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Theme {
+    pub grass: [f32; 4],
+    pub road: [f32; 4],
+    pub river: [f32; 4],
+    pub log: [f32; 4],
+    pub turtle: [f32; 4],
+    /// Tint of the second player's crab so the two can be told apart
+    pub player_two: [f32; 4],
+    /// Colors vehicles are painted in, picked at random for each vehicle
+    pub vehicles: Vec<[f32; 4]>,
+    /// Sprite sheet to use in place of the usual one, within the resources directory
+    pub sprite_sheet: Option<String>,
+    /// Crab the player moves to use in place of the usual one
+    pub small_crab: Option<String>,
+    /// Crab on the title screen to use in place of the usual one
+    pub big_crab: Option<String>,
+    /// Color of the pattern drawn over each kind of lane object, if any
+    pub pattern: Option<[f32; 4]>,
+    /// Color of the border drawn around every vehicle, if any
+    pub hazard_outline: Option<[f32; 4]>,
+    /// Color of the border drawn around every log, turtle and cubby, if any
+    pub platform_outline: Option<[f32; 4]>,
+    /// Thickness of the borders in pixels
+    pub outline_width: f32,
+}
+
+/// Palettes that can be put over any theme so the game is easier to see
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ColorMode {
+    /// The theme's own colors
+    Standard,
+    /// Colors that stay distinct with any kind of color blindness,
+    /// with a pattern on each kind of lane object
+    Colorblind,
+    /// Dark ground with bright objects, each bordered by whether
+    /// it's a hazard or a platform
+    HighContrast,
+}
+
+impl Default for Theme {
+    /// The colors the game has always had
+    fn default() -> Theme {
+        Theme {
+            grass: [0.0, 0.7, 0.23, 1.0],
+            road: [0.1, 0.2, 0.3, 1.0],
+            river: [0.3, 0.3, 1.0, 1.0],
+            log: [0.6, 0.3, 0.1, 1.0],
+            turtle: [0.5, 0.5, 0.5, 1.0],
+            player_two: [0.6, 0.8, 1.0, 1.0],
+            vehicles: vec![
+                [0.0, 0.0, 1.0, 1.0],
+                [1.0, 0.0, 0.0, 1.0],
+                [0.0, 1.0, 0.0, 1.0],
+                [1.0, 1.0, 0.0, 1.0],
+                [0.0, 1.0, 1.0, 1.0],
+                [1.0, 0.0, 1.0, 1.0],
+                [1.0, 0.5, 1.0, 1.0],
+            ],
+            sprite_sheet: None,
+            small_crab: None,
+            big_crab: None,
+            pattern: None,
+            hazard_outline: None,
+            platform_outline: None,
+            outline_width: 3.0,
+        }
+    }
+}
+
+impl Default for ColorMode {
+    fn default() -> ColorMode {
+        ColorMode::Standard
+    }
+}
+
+impl ColorMode {
+    /// Returns the name shown on the options screen
+    pub fn name(self) -> &'static str {
+        match self {
+            ColorMode::Standard => "Standard",
+            ColorMode::Colorblind => "Colorblind",
+            ColorMode::HighContrast => "High Contrast",
+        }
+    }
+
+    /// Returns the next or previous mode, wrapping around at the ends
+    pub fn step(self, forward: bool) -> ColorMode {
+        let modes = [
+            ColorMode::Standard,
+            ColorMode::Colorblind,
+            ColorMode::HighContrast,
+        ];
+        let i = modes.iter().position(|&mode| mode == self).unwrap_or(0);
+        let next = if forward { i + 1 } else { i + modes.len() - 1 };
+        modes[next % modes.len()]
+    }
+
+    /// Puts the mode's palette over a theme, keeping the theme's images
+    pub fn apply(self, theme: Theme) -> Theme {
+        match self {
+            ColorMode::Standard => theme,
+            // Okabe and Ito's palette, with logs and turtles far apart in brightness
+            ColorMode::Colorblind => Theme {
+                log: [0.55, 0.3, 0.0, 1.0],
+                turtle: [0.85, 0.85, 0.85, 1.0],
+                player_two: [0.9, 0.6, 0.0, 1.0],
+                vehicles: vec![
+                    [0.9, 0.6, 0.0, 1.0],
+                    [0.35, 0.7, 0.9, 1.0],
+                    [0.0, 0.6, 0.5, 1.0],
+                    [0.95, 0.9, 0.25, 1.0],
+                    [0.0, 0.45, 0.7, 1.0],
+                    [0.8, 0.4, 0.0, 1.0],
+                    [0.8, 0.6, 0.7, 1.0],
+                ],
+                pattern: Some([0.0, 0.0, 0.0, 0.45]),
+                ..theme
+            },
+            ColorMode::HighContrast => Theme {
+                grass: [0.0, 0.0, 0.0, 1.0],
+                road: [0.2, 0.2, 0.2, 1.0],
+                river: [0.0, 0.0, 0.4, 1.0],
+                log: [0.95, 0.95, 0.95, 1.0],
+                turtle: [0.5, 1.0, 0.5, 1.0],
+                player_two: [1.0, 1.0, 0.0, 1.0],
+                vehicles: vec![
+                    [1.0, 0.2, 0.2, 1.0],
+                    [1.0, 0.5, 0.0, 1.0],
+                    [1.0, 0.0, 1.0, 1.0],
+                ],
+                pattern: Some([0.0, 0.0, 0.0, 0.6]),
+                hazard_outline: Some([1.0, 1.0, 0.0, 1.0]),
+                platform_outline: Some([1.0, 1.0, 1.0, 1.0]),
+                outline_width: 4.0,
+                ..theme
+            },
+        }
+    }
+}
+
+/// Turns a color written in a theme file into a ggez Color
+fn colour(rgba: [f32; 4]) -> Color {
+    Color::new(rgba[0], rgba[1], rgba[2], rgba[3])
+}
+
+impl Theme {
+    /// Reads the theme with the given name, falling back to the
+    /// classic colors if it can't be read
+    pub fn load(ctx: &mut Context, name: &str) -> Theme {
+        match Theme::read(ctx, name) {
+            Ok(theme) => theme,
+            Err(e) => {
+                println!("Could not read theme {}, using the classic colors: {}", name, e);
+                Theme::default()
+            }
+        }
+    }
+
+    /// Parses a theme file
+    fn read(ctx: &mut Context, name: &str) -> GameResult<Theme> {
+        let mut json = String::new();
+        let mut file = ctx.filesystem.open(format!("{}/{}.json", THEMES_DIR, name))?;
+        file.read_to_string(&mut json)?;
+        serde_json::from_str(&json).map_err(|e| GameError::UnknownError(e.to_string()))
+    }
+
+    /// Returns the names of every theme in the themes directory in
+    /// alphabetical order, always including the default theme
+    pub fn available(ctx: &mut Context) -> Vec<String> {
+        let mut names = vec![DEFAULT_THEME.to_string()];
+        match ctx.filesystem.read_dir(THEMES_DIR) {
+            Ok(paths) => {
+                for path in paths {
+                    let is_theme = path.extension().map_or(false, |ext| ext == "json");
+                    let name = path.file_stem().and_then(|stem| stem.to_str());
+                    if let (true, Some(name)) = (is_theme, name) {
+                        names.push(name.to_string());
+                    }
+                }
+            }
+            Err(e) => println!("Could not list themes: {}", e),
+        }
+        names.sort();
+        names.dedup();
+        names
+    }
+
+    /// Returns the color of the grass, which is the background
+    pub fn grass(&self) -> Color {
+        colour(self.grass)
+    }
+
+    /// Returns the color of the road
+    pub fn road(&self) -> Color {
+        colour(self.road)
+    }
+
+    /// Returns the color of the river and the cubbies
+    pub fn river(&self) -> Color {
+        colour(self.river)
+    }
+
+    /// Returns the color of the logs
+    pub fn log(&self) -> Color {
+        colour(self.log)
+    }
+
+    /// Returns the color of the turtles
+    pub fn turtle(&self) -> Color {
+        colour(self.turtle)
+    }
+
+    /// Returns the second player's crab tint
+    pub fn player_two(&self) -> Color {
+        colour(self.player_two)
+    }
+
+    /// Returns the color of a vehicle given the paint it was picked when
+    /// built. Themes with fewer colors than that wrap back around.
+    pub fn vehicle(&self, paint: usize) -> Color {
+        if self.vehicles.is_empty() {
+            return graphics::WHITE;
+        }
+        colour(self.vehicles[paint % self.vehicles.len()])
+    }
+
+    /// Adds the pattern and border for a lane object, if the theme has them
+    pub fn mark(&self, batch: &mut DrawBatch, kind: SpriteKind, form: &Rectangle) {
+        if let Some(pattern) = self.pattern {
+            batch.add_pattern(kind, form, colour(pattern));
+        }
+        let outline = match kind {
+            SpriteKind::Car | SpriteKind::Truck => self.hazard_outline,
+            SpriteKind::Log | SpriteKind::Turtle => self.platform_outline,
+        };
+        if let Some(outline) = outline {
+            batch.add_outline(form, colour(outline), self.outline_width);
+        }
+    }
+
+    /// Adds the platform border around somewhere safe to stand, if the theme has one
+    pub fn mark_platform(&self, batch: &mut DrawBatch, form: &Rectangle) {
+        if let Some(outline) = self.platform_outline {
+            batch.add_outline(form, colour(outline), self.outline_width);
+        }
+    }
+}
+
+/// Returns the theme after or before the current one in a list of theme
+/// names, wrapping around at the ends. Used to cycle through themes on the
+/// options screen.
+pub fn step_theme(names: &[String], current: &str, forward: bool) -> String {
+    if names.is_empty() {
+        return current.to_string();
+    }
+    let position = names.iter().position(|name| name == current);
+    let next = match (position, forward) {
+        (None, _) => 0,
+        (Some(i), true) => (i + 1) % names.len(),
+        (Some(i), false) => (i + names.len() - 1) % names.len(),
+    };
+    names[next].clone()
+}
+
+/// Unit tests for Theme.
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    #[test]
+    fn missing_colors_come_from_the_classic_theme() {
+        let theme: Theme = serde_json::from_str(r#"{"grass": [0.0, 0.0, 0.0, 1.0]}"#).unwrap();
+
+        assert_eq!(theme.grass, [0.0, 0.0, 0.0, 1.0]);
+        assert_eq!(theme.road, Theme::default().road);
+        assert_eq!(theme.sprite_sheet, None);
+    }
+
+    #[test]
+    fn vehicle_paint_wraps_around_short_palettes() {
+        let mut theme = Theme::default();
+        theme.vehicles = vec![[1.0, 0.0, 0.0, 1.0], [0.0, 0.0, 1.0, 1.0]];
+
+        assert_eq!(theme.vehicle(3), theme.vehicle(1));
+    }
+
+    #[test]
+    fn themes_cycle_in_both_directions() {
+        let names = vec![
+            "classic".to_string(),
+            "night".to_string(),
+            "pastel".to_string(),
+        ];
+
+        assert_eq!(step_theme(&names, "pastel", true), "classic");
+        assert_eq!(step_theme(&names, "classic", false), "pastel");
+        assert_eq!(step_theme(&names, "gone", true), "classic");
+    }
+
+    #[test]
+    fn color_modes_keep_the_theme_images() {
+        let mut theme = Theme::default();
+        theme.sprite_sheet = Some("/night.png".to_string());
+
+        let colorblind = ColorMode::Colorblind.apply(theme.clone());
+        assert_ne!(colorblind.vehicles, theme.vehicles);
+        assert!(colorblind.pattern.is_some());
+        assert_eq!(colorblind.sprite_sheet, theme.sprite_sheet);
+
+        assert_eq!(ColorMode::Standard.apply(theme.clone()), theme);
+        assert_eq!(ColorMode::HighContrast.step(true), ColorMode::Standard);
+    }
+}
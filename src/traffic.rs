@@ -8,10 +8,10 @@ for license terms.
 use constants::{CAR_W, MAX_DELAY, MAX_NUM_OF_CARS, MAX_NUM_OF_TRUCKS, MAX_SPEED_OF_OBSTACLES,
                 MIN_DELAY, SQUARE_SIZE, TRUCK_W, WIN_H, WIN_W};
 
-use ggez::graphics::Color;
-use ggez::{Context, GameResult};
-use rand::{thread_rng, Rng};
-use sprites::Rectangle;
+use rand::Rng;
+use rng::GameRng;
+use sprites::{DrawBatch, Rectangle, SpriteKind};
+use theme::Theme;
 
 /// A Vehicle is what we use to represent the trucks & cars
 /// one would expect in the original game.
@@ -21,19 +21,64 @@ pub struct Vehicle {
     form: Rectangle,
     speed: f32,
     direction: bool,
+    /// Which of the theme's vehicle colors it is painted in
+    paint: usize,
+}
+
+/// Everything needed to put a Vehicle back exactly where it was,
+/// used when saving and resuming a game.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct VehicleState {
+    pub x: f32,
+    pub y: f32,
+    pub w: f32,
+    pub speed: f32,
+    pub direction: bool,
+    #[serde(default)]
+    pub paint: usize,
 }
 
 impl Vehicle {
     /// Returns a Vehicle with the width, starting y coordinate
     /// speed, delay (space between other objects in row), and direction.
-    pub fn construct(w: f32, y: f32, speed: f32, delay: f32, ltr_direction: bool) -> Vehicle {
+    pub fn construct(
+        w: f32,
+        y: f32,
+        speed: f32,
+        delay: f32,
+        ltr_direction: bool,
+        rng: &mut GameRng,
+    ) -> Vehicle {
         // let w = w;
         let h = SQUARE_SIZE as f32;
         let x = Vehicle::assign_starting_x(ltr_direction, w, delay);
         Vehicle {
-            form: Rectangle::construct(x, y, w, h, Vehicle::assign_color()),
+            form: Rectangle::construct(x, y, w, h),
             speed,
             direction: ltr_direction,
+            paint: Vehicle::assign_paint(rng),
+        }
+    }
+
+    /// Rebuilds a Vehicle from a saved state
+    pub fn from_state(state: &VehicleState) -> Vehicle {
+        Vehicle {
+            form: Rectangle::construct(state.x, state.y, state.w, SQUARE_SIZE),
+            speed: state.speed,
+            direction: state.direction,
+            paint: state.paint,
+        }
+    }
+
+    /// Captures the current position, speed, direction and paint
+    pub fn get_state(&self) -> VehicleState {
+        VehicleState {
+            x: self.form.x,
+            y: self.form.y,
+            w: self.form.w,
+            speed: self.speed,
+            direction: self.direction,
+            paint: self.paint,
         }
     }
 
@@ -48,28 +93,22 @@ impl Vehicle {
         }
     }
 
-    ///Assigns a random color from the set of 7 available colors
-    fn assign_color() -> Color {
-        let mut rng = thread_rng();
-        let color: u32 = rng.gen_range(0, 99999) % 7;
-
-        match color {
-            0 => Color::new(0.0, 0.0, 1.0, 1.0),
-            1 => Color::new(1.0, 0.0, 0.0, 1.0),
-            2 => Color::new(0.0, 1.0, 0.0, 1.0),
-            3 => Color::new(1.0, 1.0, 0.0, 1.0),
-            4 => Color::new(0.0, 1.0, 1.0, 1.0),
-            5 => Color::new(1.0, 0.0, 1.0, 1.0),
-            _ => Color::new(1.0, 0.5, 1.0, 0.5),
-        }
+    ///Assigns a random paint from the 7 the theme's vehicle colors are
+    ///picked from. Themes with fewer colors wrap around.
+    fn assign_paint(rng: &mut GameRng) -> usize {
+        rng.gen_range(0, 99999) % 7
     }
 
-    /// The draw function is provided by the ggez crate and is used
-    /// to create and display the rectangular graphic that is assigned
-    /// to the form attribute of the vehicle
-    fn draw(&mut self, ctx: &mut Context) -> GameResult<()> {
-        self.form.draw(ctx)?;
-        Ok(())
+    /// Adds the vehicle to the traffic layer in its paint, drawn from the
+    /// sprite sheet or as the rectangle assigned to its form if there are no sprites
+    fn draw(&self, batch: &mut DrawBatch, theme: &Theme) {
+        let kind = if self.form.w >= TRUCK_W {
+            SpriteKind::Truck
+        } else {
+            SpriteKind::Car
+        };
+        batch.add_sprite(kind, &self.form, self.direction, theme.vehicle(self.paint));
+        theme.mark(batch, kind, &self.form);
     }
 
     /// Updates the x-coordinate of the vehicle so it
@@ -96,30 +135,46 @@ impl Vehicle {
     /// Getter to acquire the left x-coordinate of the
     /// vehicle which can be used to inform
     /// when a player collides with it.
-    pub fn get_left_edge(&mut self) -> f32 {
+    pub fn get_left_edge(&self) -> f32 {
         return self.form.x;
     }
 
     /// Getter to acquire the right x-coordinate of the
     /// vehicle which can be used to inform
     /// when a player collides with it.
-    pub fn get_right_edge(&mut self) -> f32 {
+    pub fn get_right_edge(&self) -> f32 {
         return self.form.x + self.form.w;
     }
 
     /// Getter to acquire the bottom y-coordinate of the
     /// vehicle which can be used to inform
     /// when a player collides with it.
-    pub fn get_bottom_edge(&mut self) -> f32 {
+    pub fn get_bottom_edge(&self) -> f32 {
         return self.form.y;
     }
 
     /// Getter to acquire the top y-coordinate of the
     /// vehicle which can be used to inform
     /// when a player collides with it.
-    pub fn get_top_edge(&mut self) -> f32 {
+    pub fn get_top_edge(&self) -> f32 {
         return self.form.y - self.form.h;
     }
+
+    /// Getter to acquire the direction of the
+    /// vehicle, true when it is heading right
+    pub fn get_direction(&self) -> bool {
+        self.direction
+    }
+
+    /// Returns the area collisions are checked against, between the edges above
+    pub fn get_hitbox(&self) -> Rectangle {
+        Rectangle::construct(
+            self.get_left_edge(),
+            self.get_top_edge(),
+            self.get_right_edge() - self.get_left_edge(),
+            self.get_bottom_edge() - self.get_top_edge(),
+        )
+    }
 }
 
 /// The Lane manages and represents a row of vehicles (traffic).
@@ -134,14 +189,45 @@ impl Lane {
     /// transportation type, speed, and the number of vehicles
     /// available.  The values of these attributes are generated and
     /// used to create the vehicles structs.
-    pub fn construct(y_modifier: f32) -> Lane {
+    pub fn construct(y_modifier: f32, rng: &mut GameRng) -> Lane {
         let y = WIN_H as f32 - y_modifier * SQUARE_SIZE;
-        let ltr_direction = Lane::generate_direction();
-        let vehicle_type = Lane::generate_vehicle_type();
-        let num_of_vehicles = Lane::generate_number_of_vehicles(vehicle_type);
-        let speed = Lane::generate_speed();
+        let ltr_direction = Lane::generate_direction(rng);
+        let vehicle_type = Lane::generate_vehicle_type(rng);
+        let num_of_vehicles = Lane::generate_number_of_vehicles(vehicle_type, rng);
+        let speed = Lane::generate_speed(rng);
         Lane {
-            vehicles: Lane::create_vehicle(vehicle_type, y, num_of_vehicles, speed, ltr_direction),
+            vehicles: Lane::create_vehicle(
+                vehicle_type,
+                y,
+                num_of_vehicles,
+                speed,
+                ltr_direction,
+                rng,
+            ),
+        }
+    }
+
+    /// Rebuilds a Lane from the saved states of its vehicles
+    pub fn from_state(states: &[VehicleState]) -> Lane {
+        Lane {
+            vehicles: states.iter().map(Vehicle::from_state).collect(),
+        }
+    }
+
+    /// Captures the state of every vehicle in the lane
+    pub fn get_state(&self) -> Vec<VehicleState> {
+        self.vehicles.iter().map(|vehicle| vehicle.get_state()).collect()
+    }
+
+    /// Returns the x-coordinate of every vehicle in the lane
+    pub fn get_positions(&self) -> Vec<f32> {
+        self.vehicles.iter().map(|vehicle| vehicle.form.x).collect()
+    }
+
+    /// Moves every vehicle in the lane back to a recorded x-coordinate
+    pub fn set_positions(&mut self, positions: &[f32]) {
+        for (vehicle, x) in self.vehicles.iter_mut().zip(positions) {
+            vehicle.form.x = *x;
         }
     }
 
@@ -153,35 +239,62 @@ impl Lane {
         num_of_vehicles: u32,
         speed: f32,
         ltr_direction: bool,
+        rng: &mut GameRng,
     ) -> Vec<Vehicle> {
         match vehicle_type {
-            0 => Lane::create_trucks(y, num_of_vehicles, speed, ltr_direction),
-            _ => Lane::create_cars(y, num_of_vehicles, speed, ltr_direction),
+            0 => Lane::create_trucks(y, num_of_vehicles, speed, ltr_direction, rng),
+            _ => Lane::create_cars(y, num_of_vehicles, speed, ltr_direction, rng),
         }
     }
 
     /// Returns a vector of trucks, using the assigned starting y-coordinate,
     /// a randomly generated number of cars, speed, and direction.
-    fn create_trucks(y: f32, num_of_trucks: u32, speed: f32, ltr_direction: bool) -> Vec<Vehicle> {
+    fn create_trucks(
+        y: f32,
+        num_of_trucks: u32,
+        speed: f32,
+        ltr_direction: bool,
+        rng: &mut GameRng,
+    ) -> Vec<Vehicle> {
         let mut trucks = vec![];
         let mut delay = 0.0;
         while (trucks.len() as u32) < num_of_trucks {
-            trucks.push(Vehicle::construct(TRUCK_W, y, speed, delay, ltr_direction));
-
-            delay += Lane::generate_truck_delay(num_of_trucks)
+            trucks.push(Vehicle::construct(
+                TRUCK_W,
+                y,
+                speed,
+                delay,
+                ltr_direction,
+                rng,
+            ));
+
+            delay += Lane::generate_truck_delay(num_of_trucks, rng)
         }
         trucks
     }
 
     /// Returns a vector of cars, using the assigned starting y-coordinate,
     /// a randomly generated number of cars, speed, and direction.
-    fn create_cars(y: f32, num_of_cars: u32, speed: f32, ltr_direction: bool) -> Vec<Vehicle> {
+    fn create_cars(
+        y: f32,
+        num_of_cars: u32,
+        speed: f32,
+        ltr_direction: bool,
+        rng: &mut GameRng,
+    ) -> Vec<Vehicle> {
         let mut cars = vec![];
         let mut delay = 0.0;
         while (cars.len() as u32) < num_of_cars {
-            cars.push(Vehicle::construct(CAR_W, y, speed, delay, ltr_direction));
-
-            delay += Lane::generate_car_delay(num_of_cars)
+            cars.push(Vehicle::construct(
+                CAR_W,
+                y,
+                speed,
+                delay,
+                ltr_direction,
+                rng,
+            ));
+
+            delay += Lane::generate_car_delay(num_of_cars, rng)
         }
         cars
     }
@@ -191,9 +304,7 @@ impl Lane {
     /// Otherwise, a car is assinged if it returns 1-3. This allows
     /// flexiblity for changes and ensures we will typically have more
     /// cars than trucks.
-    fn generate_vehicle_type() -> u32 {
-        let mut rng = thread_rng();
-
+    fn generate_vehicle_type(rng: &mut GameRng) -> u32 {
         //0 = Trucks & 1..3 = Cars (we generally want more cars)
         rng.gen_range(0_u32, 4_u32)
     }
@@ -201,9 +312,7 @@ impl Lane {
     /// Uses the max number of trucks and cars constant to generate a bounded
     /// random number of vehicles in a row. These max numbers are scalable
     /// depending on the width of the window.
-    fn generate_number_of_vehicles(vehicle_type: u32) -> u32 {
-        let mut rng = thread_rng();
-
+    fn generate_number_of_vehicles(vehicle_type: u32, rng: &mut GameRng) -> u32 {
         match vehicle_type {
             0 => rng.gen_range(1_u32, MAX_NUM_OF_TRUCKS),
             _ => rng.gen_range(1_u32, MAX_NUM_OF_CARS),
@@ -213,18 +322,14 @@ impl Lane {
     /// Uses the obstacle max speed constant to generate a bounded random
     /// number that will be used to assign the speed of the vehicles
     /// in a row.
-    fn generate_speed() -> f32 {
-        let mut rng = thread_rng();
-
+    fn generate_speed(rng: &mut GameRng) -> f32 {
         rng.gen_range(0.5_f32, MAX_SPEED_OF_OBSTACLES)
     }
 
     /// Used to generate the delay--or space--between cars. This distance
     /// is based on the number of cars in a row (the more items there are
     /// the less space there is to leave between them)
-    fn generate_car_delay(num_of_vehicles: u32) -> f32 {
-        let mut rng = thread_rng();
-
+    fn generate_car_delay(num_of_vehicles: u32, rng: &mut GameRng) -> f32 {
         match num_of_vehicles {
             MAX_NUM_OF_CARS => MIN_DELAY,
             _ => rng.gen_range(MIN_DELAY, MAX_DELAY),
@@ -234,9 +339,7 @@ impl Lane {
     /// Used to generate the delay--or space--between trucks. This distance
     /// is based on the number of trucks in a row (the more items there are
     /// the less space there is to leave between them)
-    fn generate_truck_delay(num_of_vehicles: u32) -> f32 {
-        let mut rng = thread_rng();
-
+    fn generate_truck_delay(num_of_vehicles: u32, rng: &mut GameRng) -> f32 {
         match num_of_vehicles {
             MAX_NUM_OF_TRUCKS => MIN_DELAY + (SQUARE_SIZE * 2.0),
             _ => rng.gen_range(MIN_DELAY * 2.0, MAX_DELAY * 2.0),
@@ -248,8 +351,7 @@ impl Lane {
     /// false). The six sided die match statement was really just to give
     /// myself a laugh. This is important and let's all be glad it's not
     /// the much respected 20 sided die.
-    fn generate_direction() -> bool {
-        let mut rng = thread_rng();
+    fn generate_direction(rng: &mut GameRng) -> bool {
         let six_sided_die: u32 = rng.gen_range(0, 99999) % 6;
         match six_sided_die {
             0 => false,
@@ -261,13 +363,11 @@ impl Lane {
         }
     }
 
-    /// Calls upon the draw routine for each vehicle in the vector
-    pub fn draw_vehicles_in_lane(&mut self, ctx: &mut Context) -> GameResult<()> {
-        for vehicle in &mut self.vehicles {
-            vehicle.draw(ctx)?;
+    /// Adds each vehicle in the vector to the traffic layer
+    pub fn draw_vehicles_in_lane(&self, batch: &mut DrawBatch, theme: &Theme) {
+        for vehicle in &self.vehicles {
+            vehicle.draw(batch, theme);
         }
-
-        Ok(())
     }
 
     /// Calls upon the update routine for each vehicle in the vector
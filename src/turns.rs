@@ -0,0 +1,130 @@
+/*
+Copyright (c) 2018 Matt Carnovale, Julie Rutherford-Fields, Joshua Sander
+This work is available under the "MIT License”.
+Please see the file LICENSE in this distribution
+for license terms.
+*/
+
+use background::Cubbies;
+use characters::{Crab, CrabState};
+use constants::{START, WIN_W};
+
+/// A player's crab and cubbies, kept aside while the other player has their turn
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Player {
+    pub number: u32,
+    pub crab: CrabState,
+    pub cubbies: Vec<bool>,
+}
+
+impl Player {
+    /// Creates a player with a fresh crab and empty cubbies
+    fn new(number: u32) -> Player {
+        Player {
+            number: number,
+            crab: Crab::new(WIN_W, START as u32).get_state(),
+            cubbies: Cubbies::construct().get_state(),
+        }
+    }
+}
+
+/// Keeps track of whose turn it is in a two player game. The player
+/// taking their turn uses the crab and cubbies on the board, the
+/// other one waits here until the crab on the board dies.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Turns {
+    current: u32,
+    waiting: Player,
+}
+
+impl Turns {
+    /// Starts a game with player one on the board
+    pub fn new() -> Turns {
+        Turns {
+            current: 1,
+            waiting: Player::new(2),
+        }
+    }
+
+    /// Returns the number of the player whose turn it is
+    pub fn current(&self) -> u32 {
+        self.current
+    }
+
+    /// Returns the player waiting for their turn
+    pub fn waiting(&self) -> &Player {
+        &self.waiting
+    }
+
+    /// Returns true if the waiting player still has lives left to play
+    pub fn waiting_can_play(&self) -> bool {
+        self.waiting.crab.lives > 0
+    }
+
+    /// Gives the waiting player a fresh crab and empty cubbies
+    pub fn reset_waiting(&mut self) {
+        self.waiting = Player::new(self.waiting.number);
+    }
+
+    /// Puts the current player aside and the waiting one on the board.
+    /// Does nothing and returns false if the waiting player is out of lives.
+    pub fn pass(&mut self, crab: &mut Crab, cubbies: &mut Cubbies) -> bool {
+        if !self.waiting_can_play() {
+            return false;
+        }
+
+        let outgoing = Player {
+            number: self.current,
+            crab: crab.get_state(),
+            cubbies: cubbies.get_state(),
+        };
+        crab.set_state(&self.waiting.crab);
+        *cubbies = Cubbies::from_state(&self.waiting.cubbies);
+        self.current = self.waiting.number;
+        self.waiting = outgoing;
+        true
+    }
+}
+
+impl Default for Turns {
+    fn default() -> Turns {
+        Turns::new()
+    }
+}
+
+/// Unit tests for Turns.
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    #[test]
+    fn pass_swaps_crabs_and_cubbies() {
+        let mut turns = Turns::new();
+        let mut crab = Crab::new(WIN_W, START as u32);
+        let mut cubbies = Cubbies::construct();
+        crab.add_to_score(100);
+        cubbies.set_is_occupied(0);
+
+        assert!(turns.pass(&mut crab, &mut cubbies));
+        assert_eq!(turns.current(), 2);
+        assert_eq!(crab.get_score(), 0);
+        assert!(!cubbies.get_state()[0]);
+
+        assert!(turns.pass(&mut crab, &mut cubbies));
+        assert_eq!(turns.current(), 1);
+        assert_eq!(crab.get_score(), 100);
+        assert!(cubbies.get_state()[0]);
+    }
+
+    #[test]
+    fn no_pass_to_a_player_without_lives() {
+        let mut turns = Turns::new();
+        let mut crab = Crab::new(WIN_W, START as u32);
+        let mut cubbies = Cubbies::construct();
+        turns.waiting.crab.lives = 0;
+
+        assert!(!turns.pass(&mut crab, &mut cubbies));
+        assert_eq!(turns.current(), 1);
+    }
+}